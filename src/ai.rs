@@ -0,0 +1,116 @@
+use crate::{all_legal_moves, king_attacked, kpk_result, make_move, Color, Game, Move, Rng, Theory};
+
+// Beats any material count, more so the sooner it comes
+const MATE: i32 = 1000;
+// A king and pawn ending known to be won, worth more than any material but less than mate
+const KNOWN_WIN: i32 = 500;
+
+// Any legal move for `color`, each one as likely as the others
+pub fn random_move(game: &Game, color: Color, rng: &mut impl Rng) -> Option<Move> {
+    let moves = all_legal_moves(game, color);
+    if moves.is_empty() {
+        return None;
+    }
+    Some(moves[rng.below(moves.len())])
+}
+
+// The move that keeps the most material for `color` looking `depth` moves ahead, with
+// both sides playing their best. Pawns promote to queens.
+pub fn best_move(game: &Game, color: Color, depth: u32) -> Option<Move> {
+    let mut best = None;
+    let mut alpha = -MATE * 2;
+    for (from, to) in ordered_moves(game, color) {
+        let next = play(game, from, to);
+        let score = -search(&next, next.cur_color, depth.max(1) - 1, -MATE * 2, -alpha);
+        if best.is_none() || score > alpha {
+            alpha = score;
+            best = Some((from, to));
+        }
+    }
+    best
+}
+
+// Minimax with alpha-beta pruning, scored for the side to move
+fn search(game: &Game, color: Color, depth: u32, mut alpha: i32, beta: i32) -> i32 {
+    let moves = ordered_moves(game, color);
+    if moves.is_empty() {
+        return if king_attacked(&game.board, color) { -MATE - depth as i32 } else { 0 };
+    }
+    if depth == 0 {
+        // Material can't tell a won king and pawn ending from a drawn one
+        if let Some(theory) = kpk_result(&game.board, color) {
+            return match theory {
+                Theory::Won(winner) if winner == color => KNOWN_WIN,
+                Theory::Won(_) => -KNOWN_WIN,
+                Theory::Drawn => 0,
+            };
+        }
+        return match color {
+            Color::White => evaluate(game),
+            Color::Black => -evaluate(game),
+        };
+    }
+    for (from, to) in moves {
+        let next = play(game, from, to);
+        let score = -search(&next, next.cur_color, depth - 1, -beta, -alpha);
+        if score >= beta {
+            return beta;
+        }
+        alpha = alpha.max(score);
+    }
+    alpha
+}
+
+// Material count, positive when White is ahead
+fn evaluate(game: &Game) -> i32 {
+    game.board.0.iter().flatten().flatten().map(|p| match p.color {
+        Color::White => p.kind.value() as i32,
+        Color::Black => -(p.kind.value() as i32),
+    }).sum()
+}
+
+// Captures of the most valuable pieces first, so the pruning cuts early
+fn ordered_moves(game: &Game, color: Color) -> Vec<Move> {
+    let mut moves = all_legal_moves(game, color);
+    moves.sort_by_key(|&(_, to)| std::cmp::Reverse(game.board[to].map_or(0, |p| p.kind.value())));
+    moves
+}
+
+fn play(game: &Game, from: crate::Location, to: crate::Location) -> Game {
+    let mut next = game.position();
+    make_move(from, to, None, &mut next);
+    next.cur_color = match next.cur_color {
+        Color::Black => Color::White,
+        Color::White => Color::Black,
+    };
+    next
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::XorShift;
+
+    #[test]
+    fn seeded_random_move_is_legal() {
+        let game = Game::new();
+        let chosen = random_move(&game, Color::White, &mut XorShift::new(42)).unwrap();
+        assert!(all_legal_moves(&game, Color::White).contains(&chosen));
+        // The same seed picks the same move
+        assert_eq!(random_move(&game, Color::White, &mut XorShift::new(42)), Some(chosen));
+    }
+
+    #[test]
+    fn best_move_takes_a_free_queen() {
+        let game = Game::from_fen("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1").unwrap();
+        let square = |name| crate::parse_square(name).unwrap();
+        assert_eq!(best_move(&game, Color::White, 2), Some((square("d1"), square("d5"))));
+    }
+
+    #[test]
+    fn best_move_heads_for_a_key_square() {
+        let game = Game::from_fen("4k3/8/8/8/8/4K3/4P3/8 w - - 0 1").unwrap();
+        let (_, to) = best_move(&game, Color::White, 1).unwrap();
+        assert!(["d4", "e4", "f4"].contains(&crate::loc2move(to).as_str()));
+    }
+}
@@ -0,0 +1,2443 @@
+// TODO: Fix accessing the board and Location
+
+use std::collections::HashMap;
+
+pub mod ai;
+
+pub type Location = (usize, usize);
+// From and to
+pub type Move = (Location, Location);
+
+// What most users of the library need, for `use chess::prelude::*;`
+pub mod prelude {
+    pub use crate::{
+        all_legal_moves, apply_move, is_checked, Board, Color, FenError, Game, GameResult, Location, Move, MoveEffects, MoveError, Piece,
+        PieceKind,
+    };
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Board(pub [[Option<Piece>; 8]; 8]);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Game {
+    pub board         : Board,
+    pub cur_color     : Color,
+    pub cur_en_passant: Option<Location>,
+    pub is_checked    : bool,
+    pub rules         : Rules,
+    // Moves played so far, by either side
+    pub ply           : u32,
+    // Moves played before the position the game was loaded from, for the move numbers in FEN and PGN
+    pub start_ply     : u32,
+    // Moves since the last capture or pawn move, for the fifty-move rule
+    pub halfmove_clock: u32,
+    // Captures made by each color (indexed by `Color as usize`), per capturing piece kind
+    pub captures_by   : [[u8; 6]; 2],
+    // Material value taken by each color
+    pub material_taken: [u32; 2],
+    // The last move, if it put the side to move in check
+    pub checking_move : Option<Move>,
+    pub castling      : CastlingRights,
+    // Every move played, in order
+    pub moves         : Vec<(Location, Location, Option<PieceKind>)>,
+    // The same moves in algebraic notation, with check and mate marked
+    pub move_history  : Vec<String>,
+    // The position before each move, without the records above and below, for undo.
+    // The moves taken back, for redo.
+    pub history       : Vec<Game>,
+    pub undone        : Vec<(Location, Location, Option<PieceKind>)>,
+    // How often each position_hash has come up, for threefold repetition
+    pub repetitions   : HashMap<u64, u8>,
+}
+
+// Lost for good once the king or that rook moves, or the rook is captured
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CastlingRights {
+    white_king_side : bool,
+    white_queen_side: bool,
+    black_king_side : bool,
+    black_queen_side: bool,
+}
+
+impl CastlingRights {
+    pub fn short(&self, color: Color) -> bool {
+        match color {
+            Color::Black => self.black_king_side,
+            Color::White => self.white_king_side,
+        }
+    }
+
+    pub fn long(&self, color: Color) -> bool {
+        match color {
+            Color::Black => self.black_queen_side,
+            Color::White => self.white_queen_side,
+        }
+    }
+
+    // Drops the rights tied to a piece moving from or being captured on `loc`
+    fn clear(&mut self, loc: Location) {
+        match loc {
+            (4, 7) => { self.white_king_side = false; self.white_queen_side = false; },
+            (4, 0) => { self.black_king_side = false; self.black_queen_side = false; },
+            (7, 7) => self.white_king_side = false,
+            (0, 7) => self.white_queen_side = false,
+            (7, 0) => self.black_king_side = false,
+            (0, 0) => self.black_queen_side = false,
+            _ => {},
+        }
+    }
+}
+
+// Rule toggles for simplified variants
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rules {
+    pub en_passant: bool,
+    pub castling  : bool,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules { en_passant: true, castling: true }
+    }
+}
+
+impl std::ops::Index<Location> for Board {
+    type Output = Option<Piece>;
+    fn index(&self, index: Location) -> &Self::Output {
+        &self.0[index.1][index.0]
+    }
+}
+
+impl std::ops::IndexMut<Location> for Board {
+    fn index_mut(&mut self, index: Location) -> &mut Self::Output {
+        &mut self.0[index.1][index.0]
+    }
+}
+
+// Puts a piece on or clears a square with no rules applied, to set up test positions
+#[cfg(test)]
+impl Board {
+    fn apply_raw(&mut self, loc: Location, piece: Option<Piece>) {
+        self[loc] = piece;
+    }
+}
+
+impl std::ops::Index<(i32, i32)> for Board {
+    type Output = Option<Piece>;
+    fn index(&self, index: (i32, i32)) -> &Self::Output {
+        if index.0 < 0 || index.1 < 0 {
+            panic!("ICE");
+        }
+        &self[(index.0 as usize, index.1 as usize)]
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Color {
+    Black, White
+}
+
+impl std::fmt::Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Black => "Black",
+            Self::White => "White",
+        })
+    }
+}
+
+impl Color {
+    fn color(&self, input: &str) -> String {
+        format!("{}{}\x1b[0m",
+            match self {
+                Self::Black => "\x1b[30;1m",
+                Self::White => "\x1b[37;1m",
+            },
+            input,
+        )
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PieceKind {
+    Pawn, Knight, Bishop, Rook, Queen, King
+}
+
+impl std::fmt::Display for PieceKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.letter())
+    }
+}
+
+pub const PIECE_KINDS: [PieceKind; 6] = [PieceKind::Pawn, PieceKind::Knight, PieceKind::Bishop, PieceKind::Rook, PieceKind::Queen, PieceKind::King];
+
+impl PieceKind {
+    // Material value in pawns
+    pub fn value(&self) -> u32 {
+        match self {
+            PieceKind::Pawn => 1,
+            PieceKind::Knight | PieceKind::Bishop => 3,
+            PieceKind::Rook => 5,
+            PieceKind::Queen => 9,
+            PieceKind::King => 0,
+        }
+    }
+
+    pub fn letter(&self) -> char {
+        match self {
+            PieceKind::Pawn => 'P',
+            PieceKind::Knight => 'N',
+            PieceKind::Bishop => 'B',
+            PieceKind::Rook => 'R',
+            PieceKind::Queen => 'Q',
+            PieceKind::King => 'K',
+        }
+    }
+}
+
+// Only these can be chosen when a pawn promotes
+pub fn parse_promotion(input: &str) -> Option<PieceKind> {
+    match input.to_ascii_uppercase().as_str() {
+        "Q" => Some(PieceKind::Queen),
+        "R" => Some(PieceKind::Rook),
+        "B" => Some(PieceKind::Bishop),
+        "N" => Some(PieceKind::Knight),
+        _ => None,
+    }
+}
+
+const STRAIGHT: [(i32, i32); 4] = [(-1,0),(0,-1),(1,0),(0,1)];
+const DIAGONAL: [(i32, i32); 4] = [(-1,-1),(1,-1),(1,1),(-1,1)];
+const ALL_DIRECTIONS: [(i32, i32); 8] = [(-1,0),(-1,-1),(0,-1),(1,-1),(1,0),(1,1),(0,1),(-1,1)];
+const KNIGHT_JUMPS: [(i32, i32); 8] = [(-1,-2),(1,-2),(2,-1),(2,1),(1,2),(-1,2),(-2,1),(-2,-1)];
+
+// Offsets reachable by sliding from `loc` along each direction until blocked,
+// including the first square holding an enemy piece
+fn slide_moves(loc: Location, dirs: &[(i32, i32)], game: &Game) -> Vec<(i32, i32)> {
+    let board = &game.board;
+    let color = board[loc].unwrap().color;
+    let mut moves = Vec::new();
+    for dir in dirs {
+        let mut change = *dir;
+        let mut new_loc = (loc.0 as i32 + change.0, loc.1 as i32 + change.1);
+        while !is_out_of_bounds(new_loc) && board[new_loc].is_none() {
+            moves.push(change);
+            change = (change.0 + dir.0, change.1 + dir.1);
+            new_loc = (loc.0 as i32 + change.0, loc.1 as i32 + change.1);
+        }
+        // If it is the opposite color
+        if !is_out_of_bounds(new_loc) && board[new_loc].is_some_and(|p| p.color != color) {
+            moves.push(change);
+        }
+    }
+    moves
+}
+
+// The moves of the piece on `loc` by how it moves, whether or not they leave its king in check
+fn pseudo_moves(loc: Location, game: &Game) -> Vec<(i32, i32)> {
+    let board = &game.board;
+    let piece = board[loc].unwrap();
+    let mut moves = Vec::new();
+    match piece.kind {
+        PieceKind::Pawn => {
+            match piece.color {
+                Color::Black => {
+                    // Forward 1 step
+                    let new_loc = (loc.0 as i32, loc.1 as i32 + 1);
+                    if is_out_of_bounds(new_loc) { return moves; }
+                    let new_loc = (new_loc.0 as usize, new_loc.1 as usize);
+                    if game.board[new_loc].is_none() {
+                        moves.push((0,1));
+                    }
+
+                    if loc.1 == 1 {
+                        // Forward 2 steps
+                        let new_loc = (loc.0 as i32, loc.1 as i32 + 2);
+                        if is_out_of_bounds(new_loc) { return moves; }
+                        let new_loc = (new_loc.0 as usize, new_loc.1 as usize);
+                        // Only over an empty square, onto an empty square
+                        if moves.contains(&(0,1)) && game.board[new_loc].is_none() {
+                            moves.push((0,2));
+                        }
+                    } else {
+                        for dir in [(1,1),(-1,1)] {
+                            let new_loc = (loc.0 as i32 + dir.0, loc.1 as i32 + dir.1);
+                            if is_out_of_bounds(new_loc) { continue; }
+                            // The pawn that double stepped sits beside us, we land behind it
+                            let beside = (new_loc.0 as usize, loc.1);
+                            if game.rules.en_passant && game.cur_en_passant == Some(beside) {
+                                moves.push(dir);
+                                break;
+                            }
+                        }
+                    }
+                    for dir in [(1,1),(-1,1)] {
+                        let new_loc = (loc.0 as i32 + dir.0, loc.1 as i32 + dir.1);
+                        if is_out_of_bounds(new_loc) { continue; }
+                        let new_loc = (new_loc.0 as usize, new_loc.1 as usize);
+                        // Pawns capture diagonally, only onto enemy pieces
+                        if game.board[new_loc].is_some_and(|p| p.color != piece.color) {
+                            moves.push(dir);
+                        }
+                    }
+                },
+                Color::White => {
+                    // Forward 1 step
+                    let new_loc = (loc.0 as i32, loc.1 as i32 - 1);
+                    if is_out_of_bounds(new_loc) { return moves; }
+                    let new_loc = (new_loc.0 as usize, new_loc.1 as usize);
+                    if game.board[new_loc].is_none() {
+                        moves.push((0,-1));
+                    }
+
+                    if loc.1 == 8 - 2 {
+                        // Forward 2 steps
+                        let new_loc = (loc.0 as i32, loc.1 as i32 - 2);
+                        if is_out_of_bounds(new_loc) { return moves; }
+                        let new_loc = (new_loc.0 as usize, new_loc.1 as usize);
+                        // Only over an empty square, onto an empty square
+                        if moves.contains(&(0,-1)) && game.board[new_loc].is_none() {
+                            moves.push((0,-2));
+                        }
+                    } else {
+                        for dir in [(-1,-1),(1,-1)] {
+                            let new_loc = (loc.0 as i32 + dir.0, loc.1 as i32 + dir.1);
+                            if is_out_of_bounds(new_loc) { continue; }
+                            // The pawn that double stepped sits beside us, we land behind it
+                            let beside = (new_loc.0 as usize, loc.1);
+                            if game.rules.en_passant && game.cur_en_passant == Some(beside) {
+                                moves.push(dir);
+                                break;
+                            }
+                        }
+                    }
+                    for dir in [(-1,-1),(1,-1)] {
+                        let new_loc = (loc.0 as i32 + dir.0, loc.1 as i32 + dir.1);
+                        if is_out_of_bounds(new_loc) { continue; }
+                        let new_loc = (new_loc.0 as usize, new_loc.1 as usize);
+                        // Pawns capture diagonally, only onto enemy pieces
+                        if game.board[new_loc].is_some_and(|p| p.color != piece.color) {
+                            moves.push(dir);
+                        }
+                    }
+                },
+            }
+        }
+        PieceKind::Knight => {
+            let mut rvec = Vec::new();
+            for tile in KNIGHT_JUMPS {
+                let new_loc = (loc.0 as i32 + tile.0, loc.1 as i32 + tile.1);
+                if !is_out_of_bounds(new_loc) && (board[new_loc].is_none()
+                    || board[new_loc].unwrap().color != piece.color) {
+                    rvec.push(tile);
+                }
+            }
+            moves = rvec;
+        }
+        PieceKind::Bishop => moves = slide_moves(loc, &DIAGONAL, game),
+        PieceKind::Rook => moves = slide_moves(loc, &STRAIGHT, game),
+        // Basically both a rook and a bishop
+        PieceKind::Queen => moves = slide_moves(loc, &ALL_DIRECTIONS, game),
+        PieceKind::King => {
+            let mut rvec = Vec::new();
+            for tile in ALL_DIRECTIONS {
+                let new_loc = (loc.0 as i32 + tile.0, loc.1 as i32 + tile.1);
+                if !is_out_of_bounds(new_loc) && (board[new_loc].is_none()
+                    || board[new_loc].unwrap().color != piece.color) {
+                    rvec.push(tile);
+                }
+
+            }
+            // Castling is the king's two square move, the rook follows in `make_move`
+            if game.can_castle_short(piece.color) {
+                rvec.push((2, 0));
+            }
+            if game.can_castle_long(piece.color) {
+                rvec.push((-2, 0));
+            }
+            moves = rvec;
+        }
+    }
+    moves
+}
+
+pub fn get_moves(loc: Location, game: &Game) -> Vec<(i32, i32)> {
+    let board = &game.board;
+    let piece = board[loc].unwrap();
+    let moves = pseudo_moves(loc, game);
+
+    // Analysis copies can be missing a king, there is nothing to keep out of check then
+    if !board.0.iter().flatten().any(|p| p.is_some_and(|p| p.kind == PieceKind::King && p.color == piece.color)) {
+        return moves;
+    }
+
+    // Drop the moves that leave our own king in check
+    moves.into_iter().filter(|&mv| !leaves_king_in_check(loc, mv, game)).collect()
+}
+
+// Plays the offset `mv` from `loc` on a copy of the board and looks whether the
+// mover's king is attacked afterwards. Moves off the board count as leaving it in check.
+fn leaves_king_in_check(loc: Location, mv: (i32, i32), game: &Game) -> bool {
+    let board = &game.board;
+    let piece = board[loc].unwrap();
+    let new_loc = (loc.0 as i32 + mv.0, loc.1 as i32 + mv.1);
+    if is_out_of_bounds(new_loc) { return true; }
+    let new_loc = (new_loc.0 as usize, new_loc.1 as usize);
+    let mut new_board = *board;
+    // En passant also takes the pawn beside us off the board
+    if piece.kind == PieceKind::Pawn && new_loc.0 != loc.0 && board[new_loc].is_none() {
+        new_board[(new_loc.0, loc.1)] = None;
+    }
+    new_board[new_loc] = board[loc];
+    new_board[loc] = None;
+
+    king_attacked(&new_board, piece.color)
+}
+
+// The squares the piece on `loc` can move to, rather than offsets from it
+pub fn destinations(loc: Location, game: &Game) -> Vec<Location> {
+    get_moves(loc, game).into_iter().filter_map(|(dx, dy)| {
+        let new_loc = (loc.0 as i32 + dx, loc.1 as i32 + dy);
+        if is_out_of_bounds(new_loc) {
+            None
+        } else {
+            Some((new_loc.0 as usize, new_loc.1 as usize))
+        }
+    }).collect()
+}
+
+// Why apply_move or Piece::is_valid_move turned a move down
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MoveError {
+    // There is no piece on the square to move from
+    NoPiece,
+    WrongTurn,
+    OccupiedByOwn,
+    NotReachable,
+    WouldLeaveKingInCheck,
+    // A pawn can't promote to a king or stay a pawn
+    BadPromotion,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Piece {
+    pub kind: PieceKind,
+    pub color: Color,
+}
+
+impl std::fmt::Display for Piece {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.color.color(&self.kind.to_string()))
+    }
+}
+
+impl Piece {
+    pub fn is_valid_move(&self, from: Location, to: Location, game: &Game) -> Result<(), MoveError> {
+        if self.color != game.cur_color {
+            return Err(MoveError::WrongTurn);
+        }
+        // Check if the location you want to go to, is not occupied by your own piece
+        if game.board[to].is_some_and(|el| el.color == self.color) {
+            return Err(MoveError::OccupiedByOwn);
+        }
+        if destinations(from, game).contains(&to) {
+            return Ok(());
+        }
+        let reaches = pseudo_moves(from, game).into_iter()
+            .any(|(dx, dy)| (from.0 as i32 + dx, from.1 as i32 + dy) == (to.0 as i32, to.1 as i32));
+        Err(if reaches { MoveError::WouldLeaveKingInCheck } else { MoveError::NotReachable })
+    }
+
+    // Squares this piece attacks from `loc`. Unlike its moves that includes squares
+    // held by its own side, which it defends, and never a pawn's push forward.
+    pub fn attacks(&self, loc: Location, board: &Board) -> Vec<Location> {
+        let (dirs, slides): (&[(i32, i32)], bool) = match (self.kind, self.color) {
+            (PieceKind::Pawn, Color::Black) => (&[(-1,1),(1,1)], false),
+            (PieceKind::Pawn, Color::White) => (&[(-1,-1),(1,-1)], false),
+            (PieceKind::Knight, _) => (&KNIGHT_JUMPS, false),
+            (PieceKind::Bishop, _) => (&DIAGONAL, true),
+            (PieceKind::Rook, _) => (&STRAIGHT, true),
+            (PieceKind::Queen, _) => (&ALL_DIRECTIONS, true),
+            (PieceKind::King, _) => (&ALL_DIRECTIONS, false),
+        };
+        let mut squares = Vec::new();
+        for dir in dirs {
+            let mut new_loc = (loc.0 as i32 + dir.0, loc.1 as i32 + dir.1);
+            while !is_out_of_bounds(new_loc) {
+                squares.push((new_loc.0 as usize, new_loc.1 as usize));
+                if !slides || board[new_loc].is_some() {
+                    break;
+                }
+                new_loc = (new_loc.0 + dir.0, new_loc.1 + dir.1);
+            }
+        }
+        squares
+    }
+}
+
+static BOARD: Board = Board([
+    [ Some(Piece { kind: PieceKind::Rook, color: Color::Black }), Some(Piece { kind: PieceKind::Knight, color: Color::Black }), Some(Piece { kind: PieceKind::Bishop, color: Color::Black }), Some(Piece { kind: PieceKind::Queen, color: Color::Black }), Some(Piece { kind: PieceKind::King, color: Color::Black }), Some(Piece { kind: PieceKind::Bishop, color: Color::Black }), Some(Piece { kind: PieceKind::Knight, color: Color::Black }), Some(Piece { kind: PieceKind::Rook, color: Color::Black }) ],
+    [ Some(Piece { kind: PieceKind::Pawn, color: Color::Black }), Some(Piece { kind: PieceKind::Pawn, color: Color::Black }), Some(Piece { kind: PieceKind::Pawn, color: Color::Black }), Some(Piece { kind: PieceKind::Pawn, color: Color::Black }), Some(Piece { kind: PieceKind::Pawn, color: Color::Black }), Some(Piece { kind: PieceKind::Pawn, color: Color::Black }), Some(Piece { kind: PieceKind::Pawn, color: Color::Black }), Some(Piece { kind: PieceKind::Pawn, color: Color::Black }), ],
+    [ None, None, None, None, None, None, None, None ],
+    [ None, None, None, None, None, None, None, None ],
+    [ None, None, None, None, None, None, None, None ],
+    [ None, None, None, None, None, None, None, None ],
+    [ Some(Piece { kind: PieceKind::Pawn, color: Color::White }), Some(Piece { kind: PieceKind::Pawn, color: Color::White }), Some(Piece { kind: PieceKind::Pawn, color: Color::White }), Some(Piece { kind: PieceKind::Pawn, color: Color::White }), Some(Piece { kind: PieceKind::Pawn, color: Color::White }), Some(Piece { kind: PieceKind::Pawn, color: Color::White }), Some(Piece { kind: PieceKind::Pawn, color: Color::White }), Some(Piece { kind: PieceKind::Pawn, color: Color::White }), ],
+    [ Some(Piece { kind: PieceKind::Rook, color: Color::White }), Some(Piece { kind: PieceKind::Knight, color: Color::White }), Some(Piece { kind: PieceKind::Bishop, color: Color::White }), Some(Piece { kind: PieceKind::Queen, color: Color::White }), Some(Piece { kind: PieceKind::King, color: Color::White }), Some(Piece { kind: PieceKind::Bishop, color: Color::White }), Some(Piece { kind: PieceKind::Knight, color: Color::White }), Some(Piece { kind: PieceKind::Rook, color: Color::White }) ],
+]);
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RenderOpts {
+    // Draw the board from Black's side, rank 1 at the top
+    pub flipped : bool,
+    // Chess figurines instead of letters
+    pub unicode : bool,
+    // Alternating light and dark square backgrounds
+    pub shaded  : bool,
+    // Box-drawing frame around the squares
+    pub bordered: bool,
+}
+
+impl RenderOpts {
+    pub fn preset(style: &str) -> Option<RenderOpts> {
+        let plain = RenderOpts::default();
+        Some(match style {
+            "plain"    => plain,
+            "shaded"   => RenderOpts { shaded: true, ..plain },
+            "bordered" => RenderOpts { bordered: true, ..plain },
+            "unicode"  => RenderOpts { unicode: true, ..plain },
+            _ => return None,
+        })
+    }
+}
+
+fn piece_glyph(piece: Piece) -> char {
+    match (piece.color, piece.kind) {
+        (Color::White, PieceKind::Pawn)   => '♙',
+        (Color::White, PieceKind::Knight) => '♘',
+        (Color::White, PieceKind::Bishop) => '♗',
+        (Color::White, PieceKind::Rook)   => '♖',
+        (Color::White, PieceKind::Queen)  => '♕',
+        (Color::White, PieceKind::King)   => '♔',
+        (Color::Black, PieceKind::Pawn)   => '♟',
+        (Color::Black, PieceKind::Knight) => '♞',
+        (Color::Black, PieceKind::Bishop) => '♝',
+        (Color::Black, PieceKind::Rook)   => '♜',
+        (Color::Black, PieceKind::Queen)  => '♛',
+        (Color::Black, PieceKind::King)   => '♚',
+    }
+}
+
+// The uncolored character a piece is drawn with
+fn piece_char(piece: Piece, opts: &RenderOpts) -> char {
+    if opts.unicode {
+        piece_glyph(piece)
+    } else {
+        piece.kind.letter()
+    }
+}
+
+fn square_background(row: usize, col: usize) -> &'static str {
+    if (row + col).is_multiple_of(2) {
+        "\x1b[48;5;180m"
+    } else {
+        "\x1b[48;5;94m"
+    }
+}
+
+// Rows of the board in the order they are printed, rank 8 first unless flipped
+fn ranks_top_down(opts: &RenderOpts) -> [usize; 8] {
+    let mut rows = [0, 1, 2, 3, 4, 5, 6, 7];
+    if opts.flipped {
+        rows.reverse();
+    }
+    rows
+}
+
+// Columns of the board in the order they are printed, file a first unless flipped
+fn files_left_right(opts: &RenderOpts) -> [usize; 8] {
+    let mut cols = [0, 1, 2, 3, 4, 5, 6, 7];
+    if opts.flipped {
+        cols.reverse();
+    }
+    cols
+}
+
+fn rank_label(row: usize) -> char {
+    (b'8' - row as u8) as char
+}
+
+fn file_label(col: usize) -> char {
+    (b'a' + col as u8) as char
+}
+
+// The file letters under the board, like `abcdefgh`
+fn file_labels(opts: &RenderOpts) -> String {
+    files_left_right(opts).map(file_label).iter().collect()
+}
+
+// The file letters under the board, lined up with the squares
+fn file_label_row(opts: &RenderOpts) -> String {
+    format!("{}{}", if opts.bordered { "   " } else { "  " }, file_labels(opts))
+}
+
+// Background for the squares the last move left and landed on, drawn over the shading
+fn last_move_background(loc: Location, last_move: Option<Move>) -> Option<&'static str> {
+    match last_move {
+        Some((from, _)) if from == loc => Some("\x1b[48;5;66m"),
+        Some((_, to)) if to == loc => Some("\x1b[48;5;107m"),
+        _ => None,
+    }
+}
+
+pub fn print_board(board: &Board, last_move: Option<Move>, opts: &RenderOpts) {
+    let mut out = String::new();
+    write_board(&mut out, board, last_move, opts).unwrap();
+    print!("{}", out);
+}
+
+// The board as print_board draws it, with the squares of `last_move` highlighted
+pub fn write_board(out: &mut impl std::fmt::Write, board: &Board, last_move: Option<Move>, opts: &RenderOpts) -> std::fmt::Result {
+    if opts.bordered {
+        writeln!(out, "  ┌────────┐")?;
+    }
+    for i in ranks_top_down(opts) {
+        write!(out, "{} ", rank_label(i))?;
+        if opts.bordered {
+            write!(out, "│")?;
+        }
+        for j in files_left_right(opts) {
+            let background = last_move_background((j, i), last_move)
+                .or_else(|| opts.shaded.then(|| square_background(i, j)));
+            if let Some(x) = background {
+                write!(out, "{}", x)?;
+            }
+            match board.0[i][j] {
+                Some(x) => {
+                    write!(out, "{}", x.color.color(&piece_char(x, opts).to_string()))?;
+                },
+                None => write!(out, " ")?,
+            }
+            if background.is_some() {
+                write!(out, "\x1b[0m")?;
+            }
+        }
+        if opts.bordered {
+            write!(out, "│")?;
+        }
+        writeln!(out)?;
+    }
+    if opts.bordered {
+        writeln!(out, "  └────────┘")?;
+    }
+    writeln!(out, "{}", file_label_row(opts))
+}
+
+// The piece placement field of FEN, e.g. `rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR`
+pub fn board_fingerprint(board: &Board) -> String {
+    let mut out = String::new();
+    for (i, row) in board.0.iter().enumerate() {
+        if i != 0 {
+            out.push('/');
+        }
+        let mut empty = 0;
+        for el in row {
+            match el {
+                Some(x) => {
+                    if empty != 0 {
+                        out.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    let letter = x.kind.to_string();
+                    match x.color {
+                        Color::Black => out.push_str(&letter.to_lowercase()),
+                        Color::White => out.push_str(&letter),
+                    }
+                },
+                None => empty += 1,
+            }
+        }
+        if empty != 0 {
+            out.push_str(&empty.to_string());
+        }
+    }
+    out
+}
+
+// Why does this take 2 locations
+pub fn show_moves(from: Location, to: Location, game: &Game, opts: &RenderOpts) {
+    let board = &game.board;
+    let possible_moves = destinations(from, game);
+    if possible_moves.is_empty() {
+        println!("\x1b[34;1mThere are no available moves for \x1b[0m{}\x1b[34;1m at \x1b[35;1m{}\x1b[0m",
+            board[from].unwrap(),
+            loc2move(from),
+        );
+    }
+    print_marked_board(from, to, &possible_moves, game, opts);
+}
+
+// Prints the board with the piece on `from`, the `marked` squares and the tried `to` highlighted
+pub fn print_marked_board(from: Location, to: Location, marked: &[Location], game: &Game, opts: &RenderOpts) {
+    let board = &game.board;
+    for i in ranks_top_down(opts) {
+        print!("{} ", rank_label(i));
+        for j in files_left_right(opts) {
+            let el = board.0[i][j];
+            if (j, i) == from {
+                print!("\x1b[34;1m{}\x1b[0m", piece_char(el.unwrap(), opts));
+            } else {
+                match el {
+                    Some(x) => {
+                        if marked.contains(&(j, i)) {
+                            print!("\x1b[36;1m{}\x1b[0m", piece_char(x, opts));
+                        } else if (j, i) == to {
+                            print!("\x1b[31;1m{}\x1b[0m", piece_char(x, opts));
+                        } else {
+                            print!("{}", x.color.color(&piece_char(x, opts).to_string()));
+                        }
+                    },
+                    None => {
+                        if marked.contains(&(j, i)) {
+                            print!("\x1b[34;1m*\x1b[0m");
+                        } else if (j, i) == to {
+                            print!("\x1b[31;1mx\x1b[0m");
+                        } else {
+                            print!(" ")
+                        }
+                    },
+                }
+            }
+        }
+        println!();
+    }
+    println!("{}", file_label_row(opts));
+}
+
+// Squares the piece on `loc` attacks, whether or not it is its turn, apart from those its
+// own side holds. Unlike its moves that leaves out pawn pushes and castling, which never take anything.
+pub fn threats(loc: Location, game: &Game) -> Vec<Location> {
+    let piece = game.board[loc].unwrap();
+    piece.attacks(loc, &game.board).into_iter()
+        .filter(|&to| !game.board[to].is_some_and(|p| p.color == piece.color))
+        .collect()
+}
+
+// Static exchange evaluation: the material `from`'s side expects to come out ahead after it
+// moves to `to` and both sides keep recapturing there with their cheapest piece
+pub fn see(game: &Game, from: Location, to: Location) -> i32 {
+    // The king can only take last, so it is made too expensive to trade away
+    let see_value = |piece: Piece| match piece.kind {
+        PieceKind::King => 1000,
+        kind => kind.value() as i32,
+    };
+
+    let mut game = game.position();
+    let mut gains = vec![game.board[to].map_or(0, see_value)];
+    let mut side = game.board[from].unwrap().color;
+    game.board[to] = game.board[from];
+    game.board[from] = None;
+
+    loop {
+        side = match side {
+            Color::Black => Color::White,
+            Color::White => Color::Black,
+        };
+        let mut cheapest: Option<Location> = None;
+        for i in 0..8usize {
+            for j in 0..8usize {
+                let piece = match game.board[(j, i)] {
+                    Some(x) if x.color == side => x,
+                    _ => continue,
+                };
+                if threats((j, i), &game).contains(&to)
+                    && cheapest.is_none_or(|loc| see_value(piece) < see_value(game.board[loc].unwrap())) {
+                    cheapest = Some((j, i));
+                }
+            }
+        }
+        let Some(attacker) = cheapest else { break };
+        gains.push(see_value(game.board[to].unwrap()) - gains[gains.len() - 1]);
+        game.board[to] = game.board[attacker];
+        game.board[attacker] = None;
+    }
+
+    // Either side can stop recapturing once it would only lose more
+    while gains.len() > 1 {
+        let last = gains.pop().unwrap();
+        let prev = gains.len() - 1;
+        gains[prev] = -(-gains[prev]).max(last);
+    }
+    gains[0]
+}
+
+fn dist(a: Location, b: Location) -> usize {
+    a.1.abs_diff(b.1) + a.0.abs_diff(b.0)
+}
+
+// What a move did besides moving its piece, for the caller to report
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MoveEffects {
+    // The piece taken and where it stood, beside the pawn for en passant
+    pub captured: Option<(Piece, Location)>,
+    pub castled : Option<(Location, Location)>,
+    pub promoted: Option<PieceKind>,
+}
+
+// Plays the move on the board and updates the game state, without a word
+pub fn make_move(from: Location, to: Location, promotion: Option<PieceKind>, game: &mut Game) -> MoveEffects {
+    let board = &mut game.board;
+    let mover = board[from].unwrap();
+    let mut captured = None;
+
+    game.is_checked = false;
+    game.ply += 1;
+
+    if game.cur_en_passant.is_some() {
+        game.cur_en_passant = None;
+    }
+
+    if game.rules.en_passant && mover.kind == PieceKind::Pawn && from.0 == to.0 && dist(from, to) == 2 {
+        game.cur_en_passant = Some(to);
+    }
+
+    // A pawn moving diagonally onto an empty tile takes the pawn beside it en passant
+    if mover.kind == PieceKind::Pawn && from.0 != to.0 && board[to].is_none() {
+        captured = Some((board[(to.0, from.1)].unwrap(), (to.0, from.1)));
+        board[(to.0, from.1)] = None;
+    }
+    if let Some(taken) = board[to] {
+        captured = Some((taken, to));
+    }
+    if let Some((taken, _)) = captured {
+        game.captures_by[mover.color as usize][mover.kind as usize] += 1;
+        game.material_taken[mover.color as usize] += taken.kind.value();
+    }
+    if captured.is_some() || mover.kind == PieceKind::Pawn {
+        game.halfmove_clock = 0;
+    } else {
+        game.halfmove_clock += 1;
+    }
+
+    board[to] = board[from];
+    board[from] = None;
+
+    // A pawn reaching the last rank is promoted, to a queen unless told otherwise
+    let mut promoted = None;
+    if mover.kind == PieceKind::Pawn && (to.1 == 0 || to.1 == 7) {
+        let kind = promotion.unwrap_or(PieceKind::Queen);
+        board[to] = Some(Piece { kind, color: mover.color });
+        promoted = Some(kind);
+    }
+
+    // A king moving two squares castles, so bring the rook around it
+    let mut castled = None;
+    if mover.kind == PieceKind::King && from.0.abs_diff(to.0) == 2 {
+        let (rook_from, rook_to) = if to.0 > from.0 { ((7, to.1), (5, to.1)) } else { ((0, to.1), (3, to.1)) };
+        board[rook_to] = board[rook_from];
+        board[rook_from] = None;
+        castled = Some((rook_from, rook_to));
+    }
+    game.castling.clear(from);
+    game.castling.clear(to);
+    game.moves.push((from, to, promoted));
+
+    let them = match mover.color {
+        Color::Black => Color::White,
+        Color::White => Color::Black,
+    };
+    game.checking_move = if king_attacked(&game.board, them) { Some((from, to)) } else { None };
+
+    MoveEffects {
+        captured,
+        castled,
+        promoted,
+    }
+}
+
+// Counts the positions `depth` moves ahead with `color` to move, each promotion piece
+// separately. From the start that is 20, 400, 8902 and 197281 for depths 1 to 4.
+pub fn perft(game: &Game, color: Color, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let them = match color {
+        Color::Black => Color::White,
+        Color::White => Color::Black,
+    };
+    let mut nodes = 0;
+    for (from, to) in all_legal_moves(game, color) {
+        let promotes = game.board[from].is_some_and(|p| p.kind == PieceKind::Pawn) && (to.1 == 0 || to.1 == 7);
+        let choices: &[Option<PieceKind>] = if promotes {
+            &[Some(PieceKind::Queen), Some(PieceKind::Rook), Some(PieceKind::Bishop), Some(PieceKind::Knight)]
+        } else {
+            &[None]
+        };
+        for &promotion in choices {
+            let mut next = game.position();
+            make_move(from, to, promotion, &mut next);
+            next.cur_color = them;
+            nodes += perft(&next, them, depth - 1);
+        }
+    }
+    nodes
+}
+
+// The moves played so far as coordinates, like `e2e4 e7e5`
+pub fn moves_to_string(game: &Game) -> String {
+    game.moves.iter()
+        .map(|&(from, to, promoted)| match promoted {
+            Some(kind) => format!("{}{}{}", loc2move(from), loc2move(to), kind.letter().to_ascii_lowercase()),
+            None => format!("{}{}", loc2move(from), loc2move(to)),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChessError {
+    BadMove(String),
+    IllegalMove(String),
+    // A pawn reaching the last rank with no piece to promote to
+    MissingPromotion(String),
+}
+
+impl std::fmt::Display for ChessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::BadMove(mv) => write!(f, "{} is not a move like e2e4 or e7e8q", mv),
+            Self::IllegalMove(mv) => write!(f, "{} cannot be played here", mv),
+            Self::MissingPromotion(mv) => write!(f, "{} reaches the last rank, add the piece to promote to like {}q", mv, mv),
+        }
+    }
+}
+
+// Replays a list from moves_to_string on a new game played by `rules`
+pub fn apply_move_string(moves: &str, rules: Rules) -> Result<Game, ChessError> {
+    let mut game = Game::new().with_rules(rules);
+    for mv in moves.split_whitespace() {
+        let promotion = match mv.get(4..) {
+            Some("") => None,
+            Some(x) => Some(parse_promotion(x).ok_or_else(|| ChessError::BadMove(mv.to_string()))?),
+            None => return Err(ChessError::BadMove(mv.to_string())),
+        };
+        let (from, to) = match (mv.get(..2).and_then(parse_square), mv.get(2..4).and_then(parse_square)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return Err(ChessError::BadMove(mv.to_string())),
+        };
+        // There is nobody to ask, so the list has to say what the pawn becomes
+        let promotes = game.board[from].is_some_and(|p| p.kind == PieceKind::Pawn) && (to.1 == 0 || to.1 == 7);
+        if promotes && promotion.is_none() {
+            return Err(ChessError::MissingPromotion(mv.to_string()));
+        }
+        apply_move(&mut game, from, to, promotion).map_err(|_| ChessError::IllegalMove(mv.to_string()))?;
+    }
+    Ok(game)
+}
+
+// Plays `from` -> `to` if it is legal for the side to move, then hands the turn over.
+// Prints nothing, what the move did comes back for the caller to tell.
+pub fn apply_move(game: &mut Game, from: Location, to: Location, promotion: Option<PieceKind>) -> Result<MoveEffects, MoveError> {
+    let Some(piece) = game.board[from] else {
+        return Err(MoveError::NoPiece);
+    };
+    piece.is_valid_move(from, to, game)?;
+    if matches!(promotion, Some(PieceKind::King | PieceKind::Pawn)) {
+        return Err(MoveError::BadPromotion);
+    }
+    let mut san = move_to_san(game, from, to, promotion);
+
+    // A new move replaces whatever was taken back
+    game.undone.clear();
+    game.history.push(game.position());
+
+    let effects = make_move(from, to, promotion, game);
+    game.cur_color = match game.cur_color {
+        Color::Black => Color::White,
+        Color::White => Color::Black,
+    };
+    if is_checked(game) {
+        san.push(if has_no_valid_moves(game, game.cur_color) { '#' } else { '+' });
+    }
+    game.move_history.push(san);
+    *game.repetitions.entry(game.position_hash()).or_insert(0) += 1;
+    Ok(effects)
+}
+
+// `from` -> `to` in standard algebraic notation, before it is played and without
+// the check suffix, which only the position after it can tell
+fn move_to_san(game: &Game, from: Location, to: Location, promotion: Option<PieceKind>) -> String {
+    let piece = game.board[from].unwrap();
+    if piece.kind == PieceKind::King && from.0.abs_diff(to.0) == 2 {
+        return if to.0 > from.0 { "O-O" } else { "O-O-O" }.to_string();
+    }
+
+    let mut san = String::new();
+    let captures = game.board[to].is_some() || (piece.kind == PieceKind::Pawn && from.0 != to.0);
+    if piece.kind == PieceKind::Pawn {
+        if captures {
+            san.push(loc2move(from).chars().next().unwrap());
+        }
+    } else {
+        san.push(piece.kind.letter());
+        // Name the file, the rank or both when another piece of the same kind can get there too
+        let others: Vec<_> = all_legal_moves(game, piece.color).into_iter()
+            .filter(|&(other, dest)| dest == to && other != from && game.board[other] == Some(piece))
+            .map(|(other, _)| other)
+            .collect();
+        if !others.is_empty() {
+            let square = loc2move(from);
+            if others.iter().all(|other| other.0 != from.0) {
+                san.push_str(&square[..1]);
+            } else if others.iter().all(|other| other.1 != from.1) {
+                san.push_str(&square[1..]);
+            } else {
+                san.push_str(&square);
+            }
+        }
+    }
+    if captures {
+        san.push('x');
+    }
+    san.push_str(&loc2move(to));
+    if piece.kind == PieceKind::Pawn && (to.1 == 0 || to.1 == 7) {
+        san.push('=');
+        san.push(promotion.unwrap_or(PieceKind::Queen).letter());
+    }
+    san
+}
+
+pub fn move2loc(input: &str) -> (i32, i32) {
+    (
+        input.chars().next().unwrap() as i32 - 'a' as i32,
+        8 - (input.chars().nth(1).unwrap() as i32 - '0' as i32),
+    )
+}
+
+// Like move2loc, but rejects anything that isn't a square on the board
+pub fn parse_square(input: &str) -> Option<Location> {
+    let mut chars = input.chars();
+    let (file, rank) = (chars.next()?, chars.next()?);
+    if chars.next().is_some() || !('a'..='h').contains(&file) || !('1'..='8').contains(&rank) {
+        return None;
+    }
+    Some((file as usize - 'a' as usize, 8 - (rank as usize - '0' as usize)))
+}
+
+pub fn piece_at_algebraic(game: &Game, sq: &str) -> Option<Piece> {
+    parse_square(sq).and_then(|loc| game.board[loc])
+}
+
+// A move in standard algebraic notation like `Nf3`, `exd5`, `O-O` or `e8=Q`, as the
+// squares it moves between and the promotion piece. Anything that is not a legal move
+// for `color`, or that more than one piece could make, gives None.
+pub fn parse_san(input: &str, game: &Game, color: Color) -> Option<(Location, Location, Option<PieceKind>)> {
+    let san = input.trim_end_matches(['+', '#', '!', '?']);
+    let legal = all_legal_moves(game, color);
+
+    let row = match color {
+        Color::Black => 0,
+        Color::White => 7,
+    };
+    let castle: Option<(Location, Location)> = match san {
+        "O-O" | "0-0" => Some(((4, row), (6, row))),
+        "O-O-O" | "0-0-0" => Some(((4, row), (2, row))),
+        _ => None,
+    };
+    if let Some((from, to)) = castle {
+        let is_king = game.board[from].is_some_and(|p| p.kind == PieceKind::King);
+        return (is_king && legal.contains(&(from, to))).then_some((from, to, None));
+    }
+
+    let (san, promotion) = match san.split_once('=') {
+        Some((rest, piece)) => (rest, Some(parse_promotion(piece)?)),
+        None => (san, None),
+    };
+    let (kind, san) = match san.chars().next()? {
+        'N' => (PieceKind::Knight, &san[1..]),
+        'B' => (PieceKind::Bishop, &san[1..]),
+        'R' => (PieceKind::Rook, &san[1..]),
+        'Q' => (PieceKind::Queen, &san[1..]),
+        'K' => (PieceKind::King, &san[1..]),
+        _ => (PieceKind::Pawn, san),
+    };
+    let to = parse_square(san.get(san.len().checked_sub(2)?..)?)?;
+
+    // What is left before the destination says where the piece comes from, `x` marks a capture
+    let (mut file, mut rank) = (None, None);
+    for c in san[..san.len() - 2].chars() {
+        match c {
+            'a'..='h' => file = Some(c as usize - 'a' as usize),
+            '1'..='8' => rank = Some(8 - (c as usize - '0' as usize)),
+            'x' => {},
+            _ => return None,
+        }
+    }
+
+    let mut candidates = legal.into_iter().filter(|&(from, dest)| {
+        dest == to
+            && game.board[from].is_some_and(|p| p.kind == kind)
+            && file.is_none_or(|f| from.0 == f)
+            && rank.is_none_or(|r| from.1 == r)
+    });
+    let (from, to) = candidates.next()?;
+    if candidates.next().is_some() {
+        return None;
+    }
+    Some((from, to, promotion))
+}
+
+pub fn debugloc2move(loc: (i32, i32)) -> String {
+    format!("{}{}",
+        (b'a' + loc.0 as u8) as char,
+        8 - loc.1,
+    )
+}
+
+pub fn loc2move(loc: Location) -> String {
+    format!("{}{}",
+        (b'a' + loc.0 as u8) as char,
+        8 - loc.1,
+    )
+}
+
+pub fn is_out_of_bounds(loc: (i32, i32)) -> bool {
+    loc.0 < 0 || loc.1 < 0 || loc.0 > 7 || loc.1 > 7
+}
+
+fn get_king_location(board: &Board, color: Color) -> Location {
+    for i in 0..8usize {
+        for j in 0..8usize {
+            if let Some(piece) = board[(i, j)] {
+                if piece.kind == PieceKind::King && piece.color == color {
+                    return (i, j);
+                }
+            }
+        }
+    }
+    panic!("King already dead?");
+}
+
+// Squares attacked by `color`'s pieces, indexed like `Board.0` ([row][col]).
+// Squares behind the first piece in a line are not attacked, whatever color it is.
+pub fn attacked_squares(board: &Board, color: Color) -> [[bool; 8]; 8] {
+    let mut attacked = [[false; 8]; 8];
+    for i in 0..8usize {
+        for j in 0..8usize {
+            let piece = match board[(j, i)] {
+                Some(x) if x.color == color => x,
+                _ => continue,
+            };
+            for (col, row) in piece.attacks((j, i), board) {
+                attacked[row][col] = true;
+            }
+        }
+    }
+    attacked
+}
+
+// `color`'s own pieces that another of its pieces covers, so taking them can be answered.
+// Indexed like attacked_squares.
+pub fn defended_squares(board: &Board, color: Color) -> [[bool; 8]; 8] {
+    let mut defended = [[false; 8]; 8];
+    for i in 0..8usize {
+        for j in 0..8usize {
+            let piece = match board[(j, i)] {
+                Some(x) if x.color == color => x,
+                _ => continue,
+            };
+            for (col, row) in piece.attacks((j, i), board) {
+                if board[(col, row)].is_some_and(|p| p.color == color) {
+                    defended[row][col] = true;
+                }
+            }
+        }
+    }
+    defended
+}
+
+// Whether the side to move is in check, also recorded in `game.is_checked`
+pub fn is_checked(game: &mut Game) -> bool {
+    game.is_checked = king_attacked(&game.board, game.cur_color);
+    game.is_checked
+}
+
+// Whether `color`'s king is attacked on this board, whoever is to move
+pub(crate) fn king_attacked(board: &Board, color: Color) -> bool {
+    let loc   = get_king_location(board, color);
+    let enemy = match color {
+        Color::Black => Color::White,
+        Color::White => Color::Black,
+    };
+    attacked_squares(board, enemy)[loc.1][loc.0]
+}
+
+// Whether moving `from` -> `to` leaves the opponent in check, by playing it on a copy.
+// Pawns are taken to promote to a queen. A move that can't be played gives no check.
+pub fn gives_check(game: &Game, from: Location, to: Location) -> bool {
+    if !destinations(from, game).contains(&to) {
+        return false;
+    }
+    let mut next = game.position();
+    make_move(from, to, None, &mut next);
+    next.checking_move.is_some()
+}
+
+pub fn all_legal_moves(game: &Game, color: Color) -> Vec<Move> {
+    let mut moves = Vec::new();
+    for i in 0..8usize {
+        for j in 0..8usize {
+            if game.board[(j, i)].is_some_and(|p| p.color == color) {
+                for to in destinations((j, i), game) {
+                    moves.push(((j, i), to));
+                }
+            }
+        }
+    }
+    moves
+}
+
+// Random numbers for the AI, so tests can hand it a seeded generator
+pub trait Rng {
+    fn next_u64(&mut self) -> u64;
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+// xorshift64*, enough to pick moves reproducibly from a seed
+pub struct XorShift(u64);
+
+impl XorShift {
+    pub fn new(seed: u64) -> Self {
+        // The state must never be zero
+        XorShift((seed ^ 0x9E37_79B9_7F4A_7C15).max(1))
+    }
+}
+
+impl Rng for XorShift {
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+}
+
+pub fn has_no_valid_moves(game: &Game, color: Color) -> bool {
+    game.board.0.into_iter().enumerate().map(|(i, row)| row.into_iter().enumerate().map(|(j, cell)| {
+        match cell {
+            Some(x) if x.color == color => {
+                let loc = (j, i);
+                get_moves(loc, game).len()
+            },
+            Some(_) => 0,
+            None    => 0,
+        }
+    }).sum::<usize>()).sum::<usize>() == 0
+}
+
+// `color` is in check and has no move to get out of it
+pub fn is_checkmate(game: &Game, color: Color) -> bool {
+    king_attacked(&game.board, color) && has_no_valid_moves(game, color)
+}
+
+// Whether `attacker` has enough material to force mate against a lone king.
+// KN vs K and same colored bishops cannot, KBN vs K and the bishop pair can.
+pub fn can_force_mate(board: &Board, attacker: Color) -> bool {
+    let mut knights = 0;
+    let mut bishop_squares = [false; 2];
+    for (i, row) in board.0.iter().enumerate() {
+        for (j, cell) in row.iter().enumerate() {
+            match cell {
+                Some(p) if p.color == attacker => match p.kind {
+                    PieceKind::Pawn | PieceKind::Rook | PieceKind::Queen => return true,
+                    PieceKind::Knight => knights += 1,
+                    PieceKind::Bishop => bishop_squares[(i + j) % 2] = true,
+                    PieceKind::King => {},
+                },
+                _ => {},
+            }
+        }
+    }
+
+    match bishop_squares {
+        [true, true] => true,
+        [false, false] => false,
+        _ => knights > 0,
+    }
+}
+
+// The textbook KR vs KB draw: the bishop's king sits in the corner the bishop can't
+// reach, with the bishop beside it and not attacked. Anything else is left undecided,
+// which includes plenty of positions that are drawn as well.
+pub fn kr_vs_kb_fortress(board: &Board) -> bool {
+    let pieces: Vec<_> = (0..8usize)
+        .flat_map(|i| (0..8usize).map(move |j| (j, i)))
+        .filter_map(|loc| board[loc].map(|p| (loc, p)))
+        .collect();
+    let king_of = |color: Color| pieces.iter().filter(|(_, p)| p.kind == PieceKind::King && p.color == color).count();
+    if pieces.len() != 4 || king_of(Color::White) != 1 || king_of(Color::Black) != 1 {
+        return false;
+    }
+    let find = |kind: PieceKind| pieces.iter().find(|(_, p)| p.kind == kind).copied();
+    let (Some((_, rook)), Some((bishop_loc, bishop))) = (find(PieceKind::Rook), find(PieceKind::Bishop)) else {
+        return false;
+    };
+    if rook.color == bishop.color {
+        return false;
+    }
+    let Some(&(king_loc, _)) = pieces.iter().find(|(_, p)| p.kind == PieceKind::King && p.color == bishop.color) else {
+        return false;
+    };
+
+    let in_corner = (king_loc.0 == 0 || king_loc.0 == 7) && (king_loc.1 == 0 || king_loc.1 == 7);
+    let safe_corner = (king_loc.0 + king_loc.1) % 2 != (bishop_loc.0 + bishop_loc.1) % 2;
+    let beside = king_loc.0.abs_diff(bishop_loc.0) <= 1 && king_loc.1.abs_diff(bishop_loc.1) <= 1;
+    in_corner && safe_corner && beside && !attacked_squares(board, rook.color)[bishop_loc.1][bishop_loc.0]
+}
+
+// What theory says a position comes to with best play, ahead of the game getting there
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Theory {
+    // Holds the side that wins
+    Won(Color),
+    Drawn,
+}
+
+// King and pawn against king, by the rule of the square and the key squares.
+// None when neither rule settles it.
+pub fn kpk_result(board: &Board, side_to_move: Color) -> Option<Theory> {
+    let pieces: Vec<_> = (0..8usize)
+        .flat_map(|i| (0..8usize).map(move |j| (j, i)))
+        .filter_map(|loc| board[loc].map(|p| (loc, p)))
+        .collect();
+    if pieces.len() != 3 {
+        return None;
+    }
+    let &(pawn_loc, pawn) = pieces.iter().find(|(_, p)| p.kind == PieceKind::Pawn)?;
+    let strong = pawn.color;
+    let weak = match strong {
+        Color::Black => Color::White,
+        Color::White => Color::Black,
+    };
+    let king_of = |color: Color| pieces.iter().find(|(_, p)| p.kind == PieceKind::King && p.color == color).map(|&(loc, _)| loc);
+    let (strong_king, weak_king) = (king_of(strong)?, king_of(weak)?);
+
+    // Files and ranks from the pawn's side, the pawn promoting on rank 8
+    let relative = |(col, row): Location| match strong {
+        Color::Black => (col as i32, row as i32 + 1),
+        Color::White => (col as i32, 8 - row as i32),
+    };
+    let distance = |a: (i32, i32), b: (i32, i32)| (a.0 - b.0).abs().max((a.1 - b.1).abs());
+    let (pawn, strong_king, weak_king) = (relative(pawn_loc), relative(strong_king), relative(weak_king));
+    let won = Some(Theory::Won(strong));
+    let drawn = Some(Theory::Drawn);
+
+    // An undefended pawn next to the defending king is taken straight away
+    if side_to_move == weak && distance(weak_king, pawn) == 1 && distance(strong_king, pawn) > 1 {
+        return drawn;
+    }
+
+    // Rule of the square: the defending king can't get to the queening square in time,
+    // and the pawn's own king isn't in its way
+    let pawn_moves = if pawn.1 == 2 { 5 } else { 8 - pawn.1 };
+    let weak_moves = distance(weak_king, (pawn.0, 8)) - if side_to_move == weak { 1 } else { 0 };
+    let blocked = strong_king.0 == pawn.0 && strong_king.1 > pawn.1;
+    if weak_moves > pawn_moves && !blocked {
+        return won;
+    }
+
+    let rook_pawn = pawn.0 == 0 || pawn.0 == 7;
+    if rook_pawn {
+        // The defending king in front of a rook pawn, or next to the queening square, can't be driven off
+        let in_front = weak_king.0 == pawn.0 && weak_king.1 > pawn.1;
+        let by_corner = (weak_king.0 - pawn.0).abs() == 1 && weak_king.1 >= 7;
+        return if in_front || by_corner { drawn } else { None };
+    }
+
+    // The pawn's king on a key square wins whoever is to move. Those are the three squares
+    // two ranks ahead of the pawn, and from rank 5 on one rank ahead as well.
+    if pawn.1 <= 6 && (strong_king.0 - pawn.0).abs() <= 1 {
+        let ahead = strong_king.1 - pawn.1;
+        if ahead == 2 || (ahead == 1 && pawn.1 >= 5) {
+            return won;
+        }
+    }
+
+    // The defending king right in front of the pawn keeps the opposition
+    if weak_king.0 == pawn.0 && weak_king.1 == pawn.1 + 1 {
+        return drawn;
+    }
+    None
+}
+
+// Keys for position_hash: one for each piece on each square, then Black to move, the four
+// castling rights and the eight en passant files. splitmix64 from a fixed seed, so a hash
+// is the same on every platform and Rust release.
+const HASH_KEYS: [u64; 781] = {
+    let mut keys = [0; 781];
+    let mut state: u64 = 0;
+    let mut i = 0;
+    while i < keys.len() {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        keys[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    keys
+};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GameResult {
+    Ongoing,
+    // Holds the winner
+    Checkmate(Color),
+    Stalemate,
+    // Fifty moves by each side without a capture or a pawn move
+    FiftyMoves,
+    // The same position for the third time
+    Repetition,
+    // Neither side has enough left to mate
+    InsufficientMaterial,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum FenError {
+    MissingField(&'static str),
+    RankCount(usize),
+    // A rank that doesn't add up to 8 squares, counted from rank 8
+    BadRank(usize),
+    BadPiece(char),
+    BadColor(String),
+    BadCastling(String),
+    BadEnPassant(String),
+    BadCounter(String),
+    // Each side needs exactly one king
+    KingCount(Color),
+    // The side that just moved can't have left its king in check
+    InCheckOutOfTurn(Color),
+}
+
+impl std::fmt::Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::MissingField(field) => write!(f, "The {} field is missing", field),
+            Self::RankCount(n) => write!(f, "Found {} ranks instead of 8", n),
+            Self::BadRank(rank) => write!(f, "Rank {} does not have 8 squares", 8 - rank),
+            Self::BadPiece(c) => write!(f, "{} is not a piece letter", c),
+            Self::BadColor(x) => write!(f, "{} is not a color to move, expected w or b", x),
+            Self::BadCastling(x) => write!(f, "{} is not a castling field like KQkq or -", x),
+            Self::BadEnPassant(x) => write!(f, "{} is not the square behind a pawn that just moved two squares, or -", x),
+            Self::BadCounter(x) => write!(f, "{} is not a move counter", x),
+            Self::KingCount(color) => write!(f, "{} must have exactly one king", color),
+            Self::InCheckOutOfTurn(color) => write!(f, "{} is in check, but it is not their move", color),
+        }
+    }
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Game::new()
+    }
+}
+
+impl Game {
+    // The starting position with White to move
+    pub fn new() -> Game {
+        let mut game = Game {
+            board: BOARD,
+            cur_color: Color::White,
+            cur_en_passant: None,
+            is_checked: false,
+            rules: Rules::default(),
+            ply: 0,
+            start_ply: 0,
+            halfmove_clock: 0,
+            captures_by: [[0; 6]; 2],
+            material_taken: [0; 2],
+            checking_move: None,
+            castling: CastlingRights {
+                white_king_side: true,
+                white_queen_side: true,
+                black_king_side: true,
+                black_queen_side: true,
+            },
+            moves: Vec::new(),
+            move_history: Vec::new(),
+            history: Vec::new(),
+            undone: Vec::new(),
+            repetitions: HashMap::new(),
+        };
+        game.repetitions.insert(game.position_hash(), 1);
+        game
+    }
+
+    // A position in Forsyth-Edwards Notation, like
+    // `rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1`.
+    // The move counters may be left out.
+    pub fn from_fen(fen: &str) -> Result<Game, FenError> {
+        let mut fields = fen.split_whitespace();
+        let placement = fields.next().ok_or(FenError::MissingField("piece placement"))?;
+        let active = fields.next().ok_or(FenError::MissingField("active color"))?;
+        let castling = fields.next().ok_or(FenError::MissingField("castling"))?;
+        let en_passant = fields.next().ok_or(FenError::MissingField("en passant"))?;
+        let halfmove_clock = fields.next().map(|x| x.parse::<u32>().map_err(|_| FenError::BadCounter(x.to_string()))).transpose()?;
+        let fullmove = fields.next().map(|x| x.parse::<u32>().ok().filter(|&n| n > 0).ok_or(FenError::BadCounter(x.to_string()))).transpose()?;
+
+        let mut board = Board([[None; 8]; 8]);
+        let ranks: Vec<_> = placement.split('/').collect();
+        if ranks.len() != 8 {
+            return Err(FenError::RankCount(ranks.len()));
+        }
+        for (row, rank) in ranks.into_iter().enumerate() {
+            let mut col = 0;
+            for c in rank.chars() {
+                if let Some(empty) = c.to_digit(10) {
+                    col += empty as usize;
+                    continue;
+                }
+                let kind = match c.to_ascii_uppercase() {
+                    'P' => PieceKind::Pawn,
+                    'N' => PieceKind::Knight,
+                    'B' => PieceKind::Bishop,
+                    'R' => PieceKind::Rook,
+                    'Q' => PieceKind::Queen,
+                    'K' => PieceKind::King,
+                    _ => return Err(FenError::BadPiece(c)),
+                };
+                let color = if c.is_ascii_uppercase() { Color::White } else { Color::Black };
+                if col < 8 {
+                    board.0[row][col] = Some(Piece { kind, color });
+                }
+                col += 1;
+            }
+            if col != 8 {
+                return Err(FenError::BadRank(row));
+            }
+        }
+        for color in [Color::White, Color::Black] {
+            let kings = board.0.iter().flatten().filter(|p| **p == Some(Piece { kind: PieceKind::King, color })).count();
+            if kings != 1 {
+                return Err(FenError::KingCount(color));
+            }
+        }
+
+        let cur_color = match active {
+            "w" => Color::White,
+            "b" => Color::Black,
+            x => return Err(FenError::BadColor(x.to_string())),
+        };
+        let waiting = match cur_color {
+            Color::Black => Color::White,
+            Color::White => Color::Black,
+        };
+        if king_attacked(&board, waiting) {
+            return Err(FenError::InCheckOutOfTurn(waiting));
+        }
+
+        let mut rights = CastlingRights {
+            white_king_side: false,
+            white_queen_side: false,
+            black_king_side: false,
+            black_queen_side: false,
+        };
+        if castling != "-" {
+            for c in castling.chars() {
+                match c {
+                    'K' => rights.white_king_side = true,
+                    'Q' => rights.white_queen_side = true,
+                    'k' => rights.black_king_side = true,
+                    'q' => rights.black_queen_side = true,
+                    _ => return Err(FenError::BadCastling(castling.to_string())),
+                }
+            }
+        }
+
+        // FEN names the square behind the pawn, cur_en_passant holds the pawn itself
+        let cur_en_passant = match en_passant {
+            "-" => None,
+            x => {
+                // The pawn that just stepped two squares, the square it skipped and the one it left
+                let (skipped, pawn, left) = match (parse_square(x), cur_color) {
+                    (Some((col, 5)), Color::Black) => ((col, 5), (col, 4), (col, 6)),
+                    (Some((col, 2)), Color::White) => ((col, 2), (col, 3), (col, 1)),
+                    _ => return Err(FenError::BadEnPassant(x.to_string())),
+                };
+                if board[pawn] != Some(Piece { kind: PieceKind::Pawn, color: waiting }) || board[skipped].is_some() || board[left].is_some() {
+                    return Err(FenError::BadEnPassant(x.to_string()));
+                }
+                Some(pawn)
+            },
+        };
+
+        let start_ply = (fullmove.unwrap_or(1) - 1) * 2 + if cur_color == Color::Black { 1 } else { 0 };
+        let halfmove_clock = halfmove_clock.unwrap_or(0);
+        let mut game = Game { board, cur_color, cur_en_passant, start_ply, halfmove_clock, castling: rights, ..Game::new() };
+        is_checked(&mut game);
+        game.repetitions = HashMap::from([(game.position_hash(), 1)]);
+        Ok(game)
+    }
+
+    // The inverse of from_fen
+    pub fn to_fen(&self) -> String {
+        let mut castling = String::new();
+        for (has, c) in [
+            (self.castling.white_king_side, 'K'),
+            (self.castling.white_queen_side, 'Q'),
+            (self.castling.black_king_side, 'k'),
+            (self.castling.black_queen_side, 'q'),
+        ] {
+            if has {
+                castling.push(c);
+            }
+        }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+
+        // The square the pawn skipped over, behind it from its own side
+        let en_passant = match self.cur_en_passant {
+            Some((col, row)) => loc2move((col, if row == 4 { 5 } else { 2 })),
+            None => "-".to_string(),
+        };
+
+        format!("{} {} {} {} {} {}",
+            board_fingerprint(&self.board),
+            match self.cur_color {
+                Color::Black => 'b',
+                Color::White => 'w',
+            },
+            castling,
+            en_passant,
+            self.halfmove_clock,
+            (self.start_ply + self.ply) / 2 + 1,
+        )
+    }
+
+    // Identifies the position for repetitions: the board, the side to move, castling rights
+    // and the en passant square, but only when a pawn is there to take en passant
+    pub fn position_hash(&self) -> u64 {
+        let en_passant = self.cur_en_passant.filter(|&(col, row)| {
+            [col.wrapping_sub(1), col + 1].into_iter().any(|beside| {
+                beside < 8 && self.board[(beside, row)] == Some(Piece { kind: PieceKind::Pawn, color: self.cur_color })
+            })
+        });
+
+        let mut hash = 0;
+        for (i, row) in self.board.0.iter().enumerate() {
+            for (j, cell) in row.iter().enumerate() {
+                if let Some(p) = cell {
+                    hash ^= HASH_KEYS[(p.color as usize * 6 + p.kind as usize) * 64 + i * 8 + j];
+                }
+            }
+        }
+        if self.cur_color == Color::Black {
+            hash ^= HASH_KEYS[768];
+        }
+        let rights = [
+            self.castling.white_king_side,
+            self.castling.white_queen_side,
+            self.castling.black_king_side,
+            self.castling.black_queen_side,
+        ];
+        for (k, has) in rights.into_iter().enumerate() {
+            if has {
+                hash ^= HASH_KEYS[769 + k];
+            }
+        }
+        if let Some((col, _)) = en_passant {
+            hash ^= HASH_KEYS[773 + col];
+        }
+        hash
+    }
+
+    // King against king, with at most one knight or bishop besides, or a bishop each
+    // on the same colored squares. Nobody can be mated from here.
+    pub fn is_insufficient_material(&self) -> bool {
+        let mut minors = Vec::new();
+        for (i, row) in self.board.0.iter().enumerate() {
+            for (j, cell) in row.iter().enumerate() {
+                match cell {
+                    Some(p) if p.kind == PieceKind::King => {},
+                    Some(p) if p.kind == PieceKind::Knight || p.kind == PieceKind::Bishop => minors.push((*p, (i + j) % 2)),
+                    Some(_) => return false,
+                    None => {},
+                }
+            }
+        }
+        match minors.as_slice() {
+            [] | [_] => true,
+            [(a, a_square), (b, b_square)] => a.kind == PieceKind::Bishop && b.kind == PieceKind::Bishop && a.color != b.color && a_square == b_square,
+            _ => false,
+        }
+    }
+
+    // The state of the game for the side to move
+    pub fn result(&self) -> GameResult {
+        if is_checkmate(self, self.cur_color) {
+            GameResult::Checkmate(match self.cur_color {
+                Color::Black => Color::White,
+                Color::White => Color::Black,
+            })
+        } else if has_no_valid_moves(self, self.cur_color) {
+            GameResult::Stalemate
+        } else if self.is_insufficient_material() {
+            GameResult::InsufficientMaterial
+        } else if self.halfmove_clock >= 100 {
+            GameResult::FiftyMoves
+        } else if self.repetitions.get(&self.position_hash()).is_some_and(|&n| n >= 3) {
+            GameResult::Repetition
+        } else {
+            GameResult::Ongoing
+        }
+    }
+
+    // Whether `color` may castle king side right now: the rights are intact, the squares
+    // between king and rook are empty, and the king is not in, through or into check
+    pub fn can_castle_short(&self, color: Color) -> bool {
+        let row_num = match color {
+            Color::Black => 0,
+            Color::White => 7,
+        };
+        let attacked = attacked_squares(&self.board, match color {
+            Color::Black => Color::White,
+            Color::White => Color::Black,
+        });
+
+        self.rules.castling && self.castling.short(color) &&
+        self.board.0[row_num][4].is_some_and(|p| p.kind == PieceKind::King && p.color == color) &&
+        self.board.0[row_num][5].is_none() &&
+        self.board.0[row_num][6].is_none() &&
+        self.board.0[row_num][7].is_some_and(|p| p.kind == PieceKind::Rook && p.color == color) &&
+        !attacked[row_num][4] && !attacked[row_num][5] && !attacked[row_num][6]
+    }
+
+    // Like `can_castle_short`, towards the a-file rook. The b-file square only needs to be empty
+    pub fn can_castle_long(&self, color: Color) -> bool {
+        let row_num = match color {
+            Color::Black => 0,
+            Color::White => 7,
+        };
+        let attacked = attacked_squares(&self.board, match color {
+            Color::Black => Color::White,
+            Color::White => Color::Black,
+        });
+
+        self.rules.castling && self.castling.long(color) &&
+        self.board.0[row_num][4].is_some_and(|p| p.kind == PieceKind::King && p.color == color) &&
+        self.board.0[row_num][3].is_none() &&
+        self.board.0[row_num][2].is_none() &&
+        self.board.0[row_num][1].is_none() &&
+        self.board.0[row_num][0].is_some_and(|p| p.kind == PieceKind::Rook && p.color == color) &&
+        !attacked[row_num][4] && !attacked[row_num][3] && !attacked[row_num][2]
+    }
+
+    // This position played by `rules`, without the castling rights or en passant square they take away
+    pub fn with_rules(self, rules: Rules) -> Game {
+        let hash = self.position_hash();
+        let mut game = Game { rules, ..self };
+        if !rules.castling {
+            game.castling = CastlingRights {
+                white_king_side: false,
+                white_queen_side: false,
+                black_king_side: false,
+                black_queen_side: false,
+            };
+        }
+        if !rules.en_passant {
+            game.cur_en_passant = None;
+        }
+        // The position is still the one counted, just without those rights
+        if let Some(count) = game.repetitions.remove(&hash) {
+            game.repetitions.insert(game.position_hash(), count);
+        }
+        game
+    }
+
+    // The moves as numbered PGN movetext, like `1. e4 e5 2. Nf3`
+    pub fn pgn_movetext(&self) -> String {
+        // Games loaded from FEN start counting where the position left off
+        let start = (self.start_ply + self.ply) as usize - self.move_history.len();
+        let mut out = Vec::new();
+        for (i, san) in self.move_history.iter().enumerate() {
+            let ply = start + i;
+            if ply.is_multiple_of(2) {
+                out.push(format!("{}. {}", ply / 2 + 1, san));
+            } else if i == 0 {
+                out.push(format!("{}... {}", ply / 2 + 1, san));
+            } else {
+                out.push(san.clone());
+            }
+        }
+        out.join(" ")
+    }
+
+    // A copy of the position alone, without the record of how it came about
+    pub(crate) fn position(&self) -> Game {
+        Game {
+            moves: Vec::new(),
+            move_history: Vec::new(),
+            history: Vec::new(),
+            undone: Vec::new(),
+            repetitions: HashMap::new(),
+            ..*self
+        }
+    }
+
+    // Takes back the last move, false if there is none
+    pub fn undo(&mut self) -> bool {
+        let Some(previous) = self.history.pop() else {
+            return false;
+        };
+        // The position being left no longer counts towards a repetition
+        let hash = self.position_hash();
+        if let Some(count) = self.repetitions.get_mut(&hash) {
+            *count -= 1;
+            if *count == 0 {
+                self.repetitions.remove(&hash);
+            }
+        }
+        self.move_history.pop();
+        if let Some(mv) = self.moves.pop() {
+            self.undone.push(mv);
+        }
+        *self = Game {
+            moves: std::mem::take(&mut self.moves),
+            move_history: std::mem::take(&mut self.move_history),
+            history: std::mem::take(&mut self.history),
+            undone: std::mem::take(&mut self.undone),
+            repetitions: std::mem::take(&mut self.repetitions),
+            ..previous
+        };
+        true
+    }
+
+    // Plays the last move taken back again, false if there is none
+    pub fn redo(&mut self) -> bool {
+        let Some((from, to, promotion)) = self.undone.pop() else {
+            return false;
+        };
+        // apply_move forgets the moves taken back, the ones after this one are still there to redo
+        let undone = std::mem::take(&mut self.undone);
+        let played = apply_move(self, from, to, promotion).is_ok();
+        self.undone = undone;
+        played
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        self.result() != GameResult::Ongoing
+    }
+
+    pub fn ply(&self) -> u32 {
+        self.ply
+    }
+
+    pub fn last_move(&self) -> Option<Move> {
+        self.moves.last().map(|&(from, to, _)| (from, to))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(fen: &str) -> Board {
+        Game::from_fen(fen).unwrap().board
+    }
+
+    #[test]
+    fn start_position_fingerprint() {
+        assert_eq!(board_fingerprint(&Game::new().board), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR");
+    }
+
+    #[test]
+    fn bishop_and_knight_can_force_mate() {
+        assert!(can_force_mate(&board("8/8/8/4k3/8/8/8/2B1KN2 w - - 0 1"), Color::White));
+    }
+
+    #[test]
+    fn lone_knight_cannot_force_mate() {
+        assert!(!can_force_mate(&board("8/8/8/4k3/8/8/8/4KN2 w - - 0 1"), Color::White));
+    }
+
+    #[test]
+    fn kr_vs_kb_fortress_in_the_safe_corner() {
+        assert!(kr_vs_kb_fortress(&board("kb6/8/1K6/8/8/8/8/7R w - - 0 1")));
+        // The bishop runs on the corner's color, so the king can be mated there
+        assert!(!kr_vs_kb_fortress(&board("k7/1b6/8/2K5/8/8/8/7R w - - 0 1")));
+    }
+
+    fn sq(name: &str) -> Location {
+        parse_square(name).unwrap()
+    }
+
+    // The position after `from` -> `to`, with the other side to move
+    fn played(game: &Game, from: Location, to: Location) -> Game {
+        let mut next = game.position();
+        make_move(from, to, None, &mut next);
+        next.cur_color = match next.cur_color {
+            Color::Black => Color::White,
+            Color::White => Color::Black,
+        };
+        next
+    }
+
+    #[test]
+    fn rook_gives_direct_check() {
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert!(gives_check(&game, sq("a1"), sq("a8")));
+    }
+
+    #[test]
+    fn moving_a_blocker_gives_discovered_check() {
+        let game = Game::from_fen("4k3/8/8/8/8/8/4N3/4RK2 w - - 0 1").unwrap();
+        assert!(gives_check(&game, sq("e2"), sq("c3")));
+    }
+
+    #[test]
+    fn quiet_move_gives_no_check() {
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert!(!gives_check(&game, sq("a1"), sq("a2")));
+    }
+
+    #[test]
+    fn illegal_move_gives_no_check() {
+        // The rook can't jump its own pawn, and the pinned rook can't leave the a-file
+        let game = Game::from_fen("4k3/8/8/8/8/8/P7/R3K3 w - - 0 1").unwrap();
+        assert!(!gives_check(&game, sq("a1"), sq("a8")));
+        let game = Game::from_fen("r6k/8/8/8/8/8/R7/K7 w - - 0 1").unwrap();
+        assert!(!gives_check(&game, sq("a2"), sq("h2")));
+    }
+
+    #[test]
+    fn promotion_and_castling_give_check() {
+        let game = Game::from_fen("k7/4P3/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(gives_check(&game, sq("e7"), sq("e8")));
+        let game = Game::from_fen("5k2/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        assert!(gives_check(&game, sq("e1"), sq("g1")));
+    }
+
+    #[test]
+    fn checking_move_is_remembered() {
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        apply_move(&mut game, sq("a1"), sq("a8"), None).unwrap();
+        assert_eq!(game.checking_move, Some((sq("a1"), sq("a8"))));
+        assert!(game.is_checked);
+        // Getting out of check forgets it
+        apply_move(&mut game, sq("e8"), sq("e7"), None).unwrap();
+        assert_eq!(game.checking_move, None);
+    }
+
+    #[test]
+    fn label_order_follows_orientation() {
+        let plain = RenderOpts::default();
+        let flipped = RenderOpts { flipped: true, ..plain };
+        let ranks = |opts: &RenderOpts| ranks_top_down(opts).map(rank_label).iter().collect::<String>();
+        assert_eq!(ranks(&plain), "87654321");
+        assert_eq!(file_labels(&plain), "abcdefgh");
+        assert_eq!(ranks(&flipped), "12345678");
+        assert_eq!(file_labels(&flipped), "hgfedcba");
+    }
+
+    #[test]
+    fn start_position_has_twenty_moves() {
+        assert_eq!(all_legal_moves(&Game::new(), Color::White).len(), 20);
+    }
+
+    #[test]
+    fn moves_on_a_board_set_up_by_hand() {
+        let mut game = Game { board: Board([[None; 8]; 8]), ..Game::new() };
+        game.board.apply_raw(sq("h1"), Some(Piece { kind: PieceKind::King, color: Color::White }));
+        game.board.apply_raw(sq("a1"), Some(Piece { kind: PieceKind::Rook, color: Color::White }));
+        let moves = destinations(sq("a1"), &game);
+        assert_eq!(moves.len(), 13);
+        assert!(moves.contains(&sq("a8")) && moves.contains(&sq("g1")));
+        assert!(!moves.contains(&sq("h1")));
+    }
+
+    #[test]
+    fn piece_on_a_named_square() {
+        let game = Game::new();
+        assert_eq!(piece_at_algebraic(&game, "e1"), Some(Piece { kind: PieceKind::King, color: Color::White }));
+        assert_eq!(piece_at_algebraic(&game, "e4"), None);
+        assert_eq!(piece_at_algebraic(&game, "e9"), None);
+    }
+
+    #[test]
+    fn king_on_a_key_square_wins_kpk() {
+        let game = Game::from_fen("4k3/8/3K4/4P3/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(kpk_result(&game.board, Color::Black), Some(Theory::Won(Color::White)));
+    }
+
+    #[test]
+    fn king_in_front_of_the_pawn_draws_kpk() {
+        let game = Game::from_fen("8/8/8/4k3/4P3/4K3/8/8 w - - 0 1").unwrap();
+        assert_eq!(kpk_result(&game.board, Color::White), Some(Theory::Drawn));
+    }
+
+    #[test]
+    fn own_piece_shows_moves_enemy_piece_shows_threats() {
+        let game = Game::new();
+        assert_eq!(destinations(sq("e2"), &game), vec![sq("e3"), sq("e4")]);
+        assert_eq!(threats(sq("e7"), &game), vec![sq("d6"), sq("f6")]);
+
+        // A king with castling rights doesn't threaten the square it castles to
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/4K2R b K - 0 1").unwrap();
+        assert!(!threats(sq("e1"), &game).contains(&sq("g1")));
+        assert!(threats(sq("e1"), &game).contains(&sq("f1")));
+    }
+
+    #[test]
+    fn see_of_a_defended_square() {
+        // The pawn takes a knight and loses itself to the recapture
+        let game = Game::from_fen("4k3/8/2p5/3n4/4P3/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(see(&game, sq("e4"), sq("d5")), 2);
+        // The queen takes a pawn and is lost to the other
+        let game = Game::from_fen("4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1").unwrap();
+        assert_eq!(see(&game, sq("d1"), sq("d5")), -8);
+    }
+
+    #[test]
+    fn pawn_captures_diagonally() {
+        let game = Game::from_fen("4k3/8/8/3n4/4P3/8/8/4K3 w - - 0 1").unwrap();
+        assert!(get_moves(sq("e4"), &game).contains(&(-1, -1)));
+    }
+
+    #[test]
+    fn slides_capture_at_the_end_of_a_line_once() {
+        let game = Game::from_fen("4k3/8/7p/8/8/8/8/2B1K3 w - - 0 1").unwrap();
+        let moves = slide_moves(sq("c1"), &DIAGONAL, &game);
+        assert_eq!(moves.iter().filter(|&&mv| mv == (5,-5)).count(), 1);
+        let game = Game::from_fen("r3k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        let moves = slide_moves(sq("a1"), &STRAIGHT, &game);
+        assert_eq!(moves.iter().filter(|&&mv| mv == (0,-7)).count(), 1);
+        // A queen sees both lines
+        let game = Game::from_fen("r3k2b/8/8/8/8/8/8/Q1K5 w - - 0 1").unwrap();
+        let moves = slide_moves(sq("a1"), &ALL_DIRECTIONS, &game);
+        assert_eq!(moves.iter().filter(|&&mv| mv == (0,-7)).count(), 1);
+        assert_eq!(moves.iter().filter(|&&mv| mv == (7,-7)).count(), 1);
+    }
+
+    #[test]
+    fn white_takes_en_passant() {
+        let mut game = Game::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        assert_eq!(get_moves(sq("e5"), &game), vec![(0,-1), (-1,-1)]);
+        apply_move(&mut game, sq("e5"), sq("d6"), None).unwrap();
+        assert_eq!(game.board[sq("d6")], Some(Piece { kind: PieceKind::Pawn, color: Color::White }));
+        assert_eq!(game.board[sq("d5")], None);
+        assert_eq!(game.material_taken[Color::White as usize], 1);
+    }
+
+    #[test]
+    fn black_takes_en_passant() {
+        let mut game = Game::from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1").unwrap();
+        assert_eq!(get_moves(sq("d4"), &game), vec![(0,1), (1,1)]);
+        apply_move(&mut game, sq("d4"), sq("e3"), None).unwrap();
+        assert_eq!(game.board[sq("e3")], Some(Piece { kind: PieceKind::Pawn, color: Color::Black }));
+        assert_eq!(game.board[sq("e4")], None);
+        assert_eq!(game.material_taken[Color::Black as usize], 1);
+    }
+
+    #[test]
+    fn blocked_pawn_cannot_push() {
+        let game = Game::from_fen("4k3/3p4/3B4/8/8/8/8/4K3 b - - 0 1").unwrap();
+        let moves = get_moves(sq("d7"), &game);
+        assert!(!moves.contains(&(0, 1)));
+        assert!(!moves.contains(&(0, 2)));
+    }
+
+    #[test]
+    fn moved_pawn_pushes_beside_an_en_passant_square() {
+        let game = Game::from_fen("4k3/8/5n2/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        let moves = get_moves(sq("e5"), &game);
+        // The push, the en passant capture and the ordinary one
+        assert!(moves.contains(&(0,-1)) && moves.contains(&(-1,-1)) && moves.contains(&(1,-1)));
+    }
+
+    #[test]
+    fn is_checked_agrees_with_attacked_squares() {
+        // Every position up to two moves deep from a few busy starting points
+        let mut positions = Vec::new();
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r1bqkbnr/pppp1ppp/2n5/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 2 4",
+        ] {
+            let game = Game::from_fen(fen).unwrap();
+            for (from, to) in all_legal_moves(&game, game.cur_color) {
+                let next = played(&game, from, to);
+                for (from, to) in all_legal_moves(&next, next.cur_color) {
+                    positions.push(played(&next, from, to));
+                }
+                positions.push(next);
+            }
+        }
+
+        for mut game in positions {
+            let color = game.cur_color;
+            let enemy = match color {
+                Color::Black => Color::White,
+                Color::White => Color::Black,
+            };
+            let king = get_king_location(&game.board, color);
+            // Whether some enemy piece could move onto the king, worked out from its moves alone
+            let reached = (0..8usize).flat_map(|i| (0..8usize).map(move |j| (j, i))).any(|loc| {
+                game.board[loc].is_some_and(|p| p.color == enemy) && pseudo_moves(loc, &game).into_iter()
+                    .any(|(dx, dy)| (loc.0 as i32 + dx, loc.1 as i32 + dy) == (king.0 as i32, king.1 as i32))
+            });
+            let attacked = attacked_squares(&game.board, enemy)[king.1][king.0];
+            assert_eq!(is_checked(&mut game), attacked, "{}", game.to_fen());
+            assert_eq!(attacked, reached, "{}", game.to_fen());
+        }
+    }
+
+    #[test]
+    fn king_cannot_step_next_to_the_other_king() {
+        let game = Game::from_fen("8/8/4k3/8/4K3/8/8/8 w - - 0 1").unwrap();
+        let king = game.board[sq("e4")].unwrap();
+        assert_eq!(king.is_valid_move(sq("e4"), sq("e5"), &game), Err(MoveError::WouldLeaveKingInCheck));
+    }
+
+    #[test]
+    fn pawn_checks_diagonally_not_ahead() {
+        let mut game = Game::from_fen("4k3/8/8/3p4/4K3/8/8/8 w - - 0 1").unwrap();
+        assert!(is_checked(&mut game));
+        let mut game = Game::from_fen("4k3/8/8/4p3/4K3/8/8/8 w - - 0 1").unwrap();
+        assert!(!is_checked(&mut game));
+    }
+
+    #[test]
+    fn knight_defends_its_pawn() {
+        let board = board("4k3/8/8/8/3P4/8/4N3/4K3 w - - 0 1");
+        let defended = defended_squares(&board, Color::White);
+        let (d4, c3) = (sq("d4"), sq("c3"));
+        assert!(defended[d4.1][d4.0]);
+        // Empty squares the knight attacks are not defended
+        assert!(!defended[c3.1][c3.0]);
+    }
+
+    #[test]
+    fn castle_kingside() {
+        let mut game = Game::new();
+        game.board.apply_raw(sq("f1"), None);
+        game.board.apply_raw(sq("g1"), None);
+        apply_move(&mut game, sq("e1"), sq("g1"), None).unwrap();
+        assert_eq!(game.board[sq("g1")], Some(Piece { kind: PieceKind::King, color: Color::White }));
+        assert_eq!(game.board[sq("f1")], Some(Piece { kind: PieceKind::Rook, color: Color::White }));
+        assert_eq!(game.board[sq("h1")], None);
+        assert!(!game.castling.short(Color::White) && !game.castling.long(Color::White));
+    }
+
+    #[test]
+    fn move_list_round_trip() {
+        let mut game = Game::new();
+        for (from, to, promotion) in [("e2", "e4", None), ("d7", "d5", None), ("e4", "d5", None), ("g8", "f6", None), ("f1", "b5", None), ("c7", "c6", None)] {
+            apply_move(&mut game, sq(from), sq(to), promotion).unwrap();
+        }
+        let replayed = apply_move_string(&moves_to_string(&game), Rules::default()).unwrap();
+        assert_eq!(replayed.to_fen(), game.to_fen());
+    }
+
+    #[test]
+    fn move_list_replay_keeps_the_rules() {
+        let moves = "e2e4 a7a6 e4e5 d7d5 e5d6";
+        assert!(apply_move_string(moves, Rules::default()).is_ok());
+        assert_eq!(apply_move_string(moves, Rules { en_passant: false, ..Rules::default() }).unwrap_err(), ChessError::IllegalMove("e5d6".to_string()));
+    }
+
+    #[test]
+    fn no_en_passant_rule() {
+        let rules = Rules { en_passant: false, ..Rules::default() };
+        let loaded = Game::from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1").unwrap();
+        assert!(destinations(sq("d4"), &loaded).contains(&sq("e3")));
+        // Whether the rules come with the en passant square taken away or not
+        assert!(!destinations(sq("d4"), &Game { rules, ..loaded.clone() }).contains(&sq("e3")));
+        let game = loaded.with_rules(rules);
+        assert!(!destinations(sq("d4"), &game).contains(&sq("e3")));
+        assert_eq!(game.to_fen(), "4k3/8/8/8/3pP3/8/8/4K3 b - - 0 1");
+    }
+
+    #[test]
+    fn no_castling_rule() {
+        let rules = Rules { castling: false, ..Rules::default() };
+        let loaded = Game::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert!(get_moves(sq("e1"), &loaded).contains(&(2, 0)));
+        for game in [Game { rules, ..loaded.clone() }, loaded.with_rules(rules)] {
+            let moves = get_moves(sq("e1"), &game);
+            assert!(!moves.contains(&(2, 0)) && !moves.contains(&(-2, 0)));
+        }
+        assert_eq!(Game::new().with_rules(rules).to_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1");
+    }
+
+    #[test]
+    fn promote_to_a_knight() {
+        let mut game = Game::from_fen("8/4P3/8/8/8/8/8/k3K3 w - - 0 1").unwrap();
+        apply_move(&mut game, sq("e7"), sq("e8"), Some(PieceKind::Knight)).unwrap();
+        assert_eq!(game.board[sq("e8")], Some(Piece { kind: PieceKind::Knight, color: Color::White }));
+    }
+
+    #[test]
+    fn move_list_promotion_needs_a_piece() {
+        let moves = "a2a4 h7h5 a4a5 h5h4 a5a6 h4h3 a6b7 h3g2 b7a8";
+        let err = apply_move_string(moves, Rules::default()).unwrap_err();
+        assert_eq!(err, ChessError::MissingPromotion("b7a8".to_string()));
+        assert_eq!(err.to_string(), "b7a8 reaches the last rank, add the piece to promote to like b7a8q");
+        let game = apply_move_string(&format!("{}q", moves), Rules::default()).unwrap();
+        assert_eq!(game.board[sq("a8")], Some(Piece { kind: PieceKind::Queen, color: Color::White }));
+    }
+
+    #[test]
+    fn promote_to_a_king_or_pawn() {
+        let mut game = Game::from_fen("8/4P3/8/8/8/8/8/k3K3 w - - 0 1").unwrap();
+        assert_eq!(apply_move(&mut game, sq("e7"), sq("e8"), Some(PieceKind::King)), Err(MoveError::BadPromotion));
+        assert_eq!(apply_move(&mut game, sq("e7"), sq("e8"), Some(PieceKind::Pawn)), Err(MoveError::BadPromotion));
+        assert_eq!(game.board[sq("e7")], Some(Piece { kind: PieceKind::Pawn, color: Color::White }));
+    }
+
+    #[test]
+    fn fools_mate_is_checkmate() {
+        let game = apply_move_string("f2f3 e7e5 g2g4 d8h4", Rules::default()).unwrap();
+        assert!(is_checkmate(&game, Color::White));
+        assert_eq!(game.result(), GameResult::Checkmate(Color::Black));
+    }
+
+    #[test]
+    fn terminal_after_mate_only() {
+        assert!(!Game::new().is_terminal());
+        let game = apply_move_string("f2f3 e7e5 g2g4", Rules::default()).unwrap();
+        assert!(!game.is_terminal());
+        let game = apply_move_string("f2f3 e7e5 g2g4 d8h4", Rules::default()).unwrap();
+        assert!(game.is_terminal());
+    }
+
+    #[test]
+    fn fen_round_trip() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+            "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3",
+        ] {
+            assert_eq!(Game::from_fen(fen).unwrap().to_fen(), fen);
+        }
+    }
+
+    #[test]
+    fn fen_en_passant_needs_the_pawn_that_moved() {
+        let bad = FenError::BadEnPassant("e3".to_string());
+        // No pawn on e4
+        assert_eq!(Game::from_fen("4k3/8/8/8/3p4/8/8/4K3 b - e3 0 1"), Err(bad.clone()));
+        // Something on the skipped square
+        assert_eq!(Game::from_fen("4k3/8/8/8/3pP3/4N3/8/4K3 b - e3 0 1"), Err(bad));
+    }
+
+    #[test]
+    fn fen_side_not_to_move_in_check() {
+        let err = Game::from_fen("4k3/8/8/8/8/8/8/4RK2 w - - 0 1").unwrap_err();
+        assert_eq!(err, FenError::InCheckOutOfTurn(Color::Black));
+        assert!(Game::from_fen("4k3/8/8/8/8/8/8/4RK2 b - - 0 1").is_ok());
+    }
+
+    #[test]
+    fn loaded_position_can_already_be_over() {
+        assert_eq!(Game::from_fen("k7/1Q6/1K6/8/8/8/8/8 b - - 0 1").unwrap().result(), GameResult::Checkmate(Color::White));
+        assert_eq!(Game::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap().result(), GameResult::Stalemate);
+    }
+
+    #[test]
+    fn loaded_game_counts_plies_from_zero() {
+        let mut game = Game::from_fen("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3").unwrap();
+        assert_eq!(game.ply(), 0);
+        apply_move(&mut game, sq("g8"), sq("f6"), None).unwrap();
+        apply_move(&mut game, sq("b1"), sq("c3"), None).unwrap();
+        assert_eq!(game.ply(), 2);
+        assert_eq!(game.pgn_movetext(), "3... Nf6 4. Nc3");
+        assert!(game.to_fen().ends_with(" 4"));
+    }
+
+    #[test]
+    fn start_position_fen() {
+        assert_eq!(Game::new().to_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    }
+
+    #[test]
+    fn parse_san_knight_move() {
+        assert_eq!(parse_san("Nf3", &Game::new(), Color::White), Some((sq("g1"), sq("f3"), None)));
+    }
+
+    #[test]
+    fn parse_san_castling() {
+        let mut game = Game::new();
+        assert_eq!(parse_san("O-O", &game, Color::White), None);
+        game.board.apply_raw(sq("f1"), None);
+        game.board.apply_raw(sq("g1"), None);
+        assert_eq!(parse_san("O-O", &game, Color::White), Some((sq("e1"), sq("g1"), None)));
+    }
+
+    #[test]
+    fn pgn_movetext_numbers_the_moves() {
+        let game = apply_move_string("e2e4 e7e5 g1f3 b8c6 f1b5", Rules::default()).unwrap();
+        assert_eq!(game.pgn_movetext(), "1. e4 e5 2. Nf3 Nc6 3. Bb5");
+        let game = apply_move_string("f2f3 e7e5 g2g4 d8h4", Rules::default()).unwrap();
+        assert_eq!(game.pgn_movetext(), "1. f3 e5 2. g4 Qh4#");
+    }
+
+    #[test]
+    fn undo_returns_to_the_start() {
+        let mut game = Game::new();
+        apply_move(&mut game, sq("e2"), sq("e4"), None).unwrap();
+        let played = game.clone();
+        assert!(game.undo());
+        assert_eq!(game.ply(), 0);
+        assert_eq!(game.undone, vec![(sq("e2"), sq("e4"), None)]);
+        assert_eq!(Game { undone: Vec::new(), ..game.clone() }, Game::new());
+        assert!(game.redo());
+        assert_eq!(game, played);
+        assert!(!game.redo());
+    }
+
+    #[test]
+    fn perft_start_position() {
+        let game = Game::new();
+        assert_eq!(perft(&game, Color::White, 1), 20);
+        assert_eq!(perft(&game, Color::White, 2), 400);
+        assert_eq!(perft(&game, Color::White, 3), 8902);
+    }
+
+    #[test]
+    fn perft_kiwipete() {
+        let game = Game::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(perft(&game, Color::White, 1), 48);
+        assert_eq!(perft(&game, Color::White, 2), 2039);
+        assert_eq!(perft(&game, Color::White, 3), 97862);
+    }
+
+    #[test]
+    fn perft_en_passant_and_checks() {
+        let game = Game::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap();
+        assert_eq!(perft(&game, Color::White, 4), 43238);
+    }
+
+    #[test]
+    fn move_onto_own_piece() {
+        let game = Game::new();
+        let rook = game.board[sq("a1")].unwrap();
+        assert_eq!(rook.is_valid_move(sq("a1"), sq("a2"), &game), Err(MoveError::OccupiedByOwn));
+    }
+
+    #[test]
+    fn move_out_of_reach() {
+        let game = Game::new();
+        let knight = game.board[sq("b1")].unwrap();
+        assert_eq!(knight.is_valid_move(sq("b1"), sq("b3"), &game), Err(MoveError::NotReachable));
+        assert_eq!(knight.is_valid_move(sq("b1"), sq("c3"), &game), Ok(()));
+    }
+
+    #[test]
+    fn apply_move_gives_the_reason() {
+        let mut game = Game::new();
+        assert_eq!(apply_move(&mut game, sq("e4"), sq("e5"), None), Err(MoveError::NoPiece));
+        assert_eq!(apply_move(&mut game, sq("e7"), sq("e5"), None), Err(MoveError::WrongTurn));
+        assert_eq!(apply_move(&mut game, sq("b1"), sq("b3"), None), Err(MoveError::NotReachable));
+        assert_eq!(game, Game::new());
+    }
+
+    #[test]
+    fn fifty_moves_of_knight_shuffles_draw() {
+        let shuffle = "g1f3 g8f6 f3g1 f6g8 ".repeat(25);
+        let mut game = apply_move_string(&shuffle, Rules::default()).unwrap();
+        assert_eq!(game.halfmove_clock, 100);
+        assert_eq!(game.result(), GameResult::FiftyMoves);
+
+        // A pawn move starts the count again
+        apply_move(&mut game, sq("e2"), sq("e4"), None).unwrap();
+        assert_eq!(game.halfmove_clock, 0);
+    }
+
+    #[test]
+    fn insufficient_material() {
+        let material = |fen: &str| Game::from_fen(fen).unwrap().is_insufficient_material();
+        assert!(material("4k3/8/8/8/8/8/8/4K3 w - - 0 1"));
+        assert!(!material("4k3/8/8/8/8/8/8/R3K3 w - - 0 1"));
+        // Bishops on the same colored squares, then on different ones
+        assert!(material("2b1k3/8/8/8/8/8/8/4KB2 w - - 0 1"));
+        assert!(!material("4kb2/8/8/8/8/8/8/4KB2 w - - 0 1"));
+    }
+
+    #[test]
+    fn knight_shuffles_repeat_the_position() {
+        let mut game = apply_move_string("g1f3 g8f6 f3g1 f6g8", Rules::default()).unwrap();
+        assert_eq!(game.result(), GameResult::Ongoing);
+        for (from, to) in [("g1", "f3"), ("g8", "f6"), ("f3", "g1"), ("f6", "g8")] {
+            apply_move(&mut game, sq(from), sq(to), None).unwrap();
+        }
+        assert_eq!(game.position_hash(), Game::new().position_hash());
+        assert_eq!(game.result(), GameResult::Repetition);
+    }
+
+    #[test]
+    fn position_hash_is_fixed() {
+        // The keys are fixed, so this may only change together with them
+        assert_eq!(Game::new().position_hash(), 17234431138423775338);
+        // Side to move and castling rights are part of the position
+        let start = Game::new();
+        assert_ne!(Game { cur_color: Color::Black, ..start.clone() }.position_hash(), start.position_hash());
+        let mut no_castling = start.clone();
+        no_castling.castling.clear(sq("e1"));
+        assert_ne!(no_castling.position_hash(), start.position_hash());
+    }
+
+    #[test]
+    fn pinned_bishop_cannot_leave_the_file() {
+        let game = Game::from_fen("k3r3/8/8/8/8/8/4B3/4K3 w - - 0 1").unwrap();
+        assert!(!game.is_checked);
+        assert!(get_moves(sq("e2"), &game).is_empty());
+        assert!(pseudo_moves(sq("e2"), &game).iter().all(|&mv| leaves_king_in_check(sq("e2"), mv, &game)));
+    }
+
+    #[test]
+    fn knight_in_a_rook_check_takes_or_blocks() {
+        let game = Game::from_fen("4r2k/8/3N4/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let mut moves = destinations(sq("d6"), &game);
+        moves.sort();
+        assert_eq!(moves, vec![sq("e8"), sq("e4")]);
+    }
+
+    #[test]
+    fn glyphs_for_every_piece() {
+        let glyphs = |color: Color| PIECE_KINDS.iter().map(|&kind| piece_glyph(Piece { kind, color })).collect::<String>();
+        assert_eq!(glyphs(Color::White), "♙♘♗♖♕♔");
+        assert_eq!(glyphs(Color::Black), "♟♞♝♜♛♚");
+    }
+
+    #[test]
+    fn last_move_squares_get_their_own_background() {
+        let last_move = Some((sq("e2"), sq("e4")));
+        let from = last_move_background(sq("e2"), last_move);
+        let to = last_move_background(sq("e4"), last_move);
+        assert!(from.is_some() && to.is_some() && from != to);
+        assert_eq!(last_move_background(sq("d2"), last_move), None);
+        assert_eq!(last_move_background(sq("e2"), None), None);
+    }
+
+    #[test]
+    fn board_styles() {
+        let board = Game::new().board;
+        let draw = |style: &str| {
+            let mut out = String::new();
+            write_board(&mut out, &board, None, &RenderOpts::preset(style).unwrap()).unwrap();
+            out
+        };
+        let (plain, shaded, bordered, unicode) = (draw("plain"), draw("shaded"), draw("bordered"), draw("unicode"));
+        assert!(plain.contains('K') && plain.ends_with("\n  abcdefgh\n"));
+        assert!(!plain.contains("\x1b[48;5;") && !plain.contains('│') && !plain.contains('♔'));
+        assert!(shaded.contains("\x1b[48;5;180m") && shaded.contains("\x1b[48;5;94m"));
+        assert!(bordered.starts_with("  ┌────────┐\n") && bordered.contains("│\n") && bordered.ends_with("  └────────┘\n   abcdefgh\n"));
+        assert!(unicode.contains('♔') && unicode.contains('♚') && !unicode.contains('K'));
+        assert!(RenderOpts::preset("fancy").is_none());
+    }
+}
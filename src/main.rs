@@ -1,636 +1,602 @@
-// TODO: Fix accessing the board and Location
+use std::io::IsTerminal;
 
-type Location = (usize, usize);
+use chess::*;
 
-#[derive(Copy, Clone, Debug)]
-struct Board([[Option<Piece>; 8]; 8]);
+static QUIET: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
 
-#[derive(Copy, Clone, Debug)]
-struct Game {
-    board         : Board,
-    cur_color     : Color,
-    cur_en_passant: Option<Location>,
-    is_checked    : bool,
+fn is_quiet() -> bool {
+    QUIET.load(std::sync::atomic::Ordering::Relaxed)
 }
 
-impl std::ops::Index<Location> for Board {
-    type Output = Option<Piece>;
-    fn index(&self, index: Location) -> &Self::Output {
-        &self.0[index.1][index.0]
-    }
-}
-
-impl std::ops::IndexMut<Location> for Board {
-    fn index_mut(&mut self, index: Location) -> &mut Self::Output {
-        &mut self.0[index.1][index.0]
-    }
+fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, std::sync::atomic::Ordering::Relaxed);
 }
 
-impl std::ops::Index<(i32, i32)> for Board {
-    type Output = Option<Piece>;
-    fn index(&self, index: (i32, i32)) -> &Self::Output {
-        if index.0 < 0 || index.1 < 0 {
-            panic!("ICE");
+// println! for informational output, which --quiet silences
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if !is_quiet() {
+            println!($($arg)*);
         }
-        &self[(index.0 as usize, index.1 as usize)]
-    }
+    };
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
-enum Color {
-    Black, White
+#[derive(Clone, Debug)]
+struct Players {
+    white: String,
+    black: String,
 }
 
-impl ToString for Color {
-    fn to_string(&self) -> String {
-        match self {
-            Self::Black => "Black",
-            Self::White => "White",
-        }.to_string()
+impl Default for Players {
+    fn default() -> Self {
+        Players {
+            white: Color::White.to_string(),
+            black: Color::Black.to_string(),
+        }
     }
 }
 
-impl Color {
-    fn color(&self, input: &str) -> String {
-        format!("{}{}\x1b[0m",
-            match self {
-                Self::Black => "\x1b[30;1m",
-                Self::White => "\x1b[37;1m",
-            },
-            input,
-        )
+impl Players {
+    fn name(&self, color: Color) -> &str {
+        match color {
+            Color::Black => &self.black,
+            Color::White => &self.white,
+        }
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
-enum PieceKind {
-    Pawn, Knight, Bishop, Rook, Queen, King
-}
-
-impl ToString for PieceKind {
-    fn to_string(&self) -> String {
-        match self {
-            PieceKind::Pawn => 'P',
-            PieceKind::Knight => 'N',
-            PieceKind::Bishop => 'B',
-            PieceKind::Rook => 'R',
-            PieceKind::Queen => 'Q',
-            PieceKind::King => 'K',
-        }.to_string()
-    }
+#[derive(Clone, Debug, Default)]
+struct Options {
+    players: Players,
+    rules  : Rules,
+    quiet  : bool,
+    random_opening: Option<(u64, u32)>,
+    // The side the computer plays, and how far it looks ahead. Random moves without a depth.
+    ai     : Option<Color>,
+    depth  : Option<u32>,
+    render : RenderOpts,
+    // Turn the board to face whoever is to move, for two players sharing a terminal
+    follow_turn: bool,
+    no_prompt: bool,
+    moves  : Option<String>,
+    fen    : Option<String>,
 }
 
-fn get_moves(loc: Location, game: &Game) -> Vec<(i32, i32)> {
-    let board = &game.board;
-    let piece = board[loc].unwrap();
-    let mut moves = Vec::new();
-    match piece.kind {
-        PieceKind::Pawn => {
-            match piece.color {
-                Color::Black => {
-                    // Forward 1 step
-                    let new_loc = (loc.0 as i32 + 0, loc.1 as i32 + 1);
-                    if is_out_of_bounds(new_loc) { return moves; }
-                    let new_loc = (new_loc.0 as usize, new_loc.1 as usize);
-                    if !game.board[new_loc].is_some_and(|p| p.color == game.cur_color) {
-                        moves.push((0,1));
-                    }
-
-                    if loc.1 == 1 {
-                        // Forward 2 steps
-                        let new_loc = (loc.0 as i32 + 0, loc.1 as i32 + 2);
-                        if is_out_of_bounds(new_loc) { return moves; }
-                        let new_loc = (new_loc.0 as usize, new_loc.1 as usize);
-                        if !game.board[new_loc].is_some_and(|p| p.color == game.cur_color) {
-                            moves.push((0,2));
-                        }
-                    } else {
-                        for dir in [(1,1),(-1,1)] {
-                            let new_loc = (loc.0 as i32 + dir.0, loc.1 as i32 + dir.1);
-                            if is_out_of_bounds(new_loc) { continue; }
-                            let new_loc = (new_loc.0 as usize, new_loc.1 as usize);
-                            if game.cur_en_passant == Some(new_loc) {
-                                moves.push(dir);
-                                break;
-                            }
-                        }
-                    }
-                    for dir in [(1,1),(-1,1)] {
-                        let new_loc = (loc.0 as i32 + dir.0, loc.1 as i32 + dir.1);
-                        if is_out_of_bounds(new_loc) { continue; }
-                        let new_loc = (new_loc.0 as usize, new_loc.1 as usize);
-                        if game.board[new_loc].is_some_and(|p| p.color != game.cur_color) {
-                            moves.push(dir);
-                        }
-                    }
-                },
-                Color::White => {
-                    // Forward 1 step
-                    let new_loc = (loc.0 as i32 + 0, loc.1 as i32 - 1);
-                    if is_out_of_bounds(new_loc) { return moves; }
-                    let new_loc = (new_loc.0 as usize, new_loc.1 as usize);
-                    if !game.board[new_loc].is_some_and(|p| p.color == game.cur_color) {
-                        moves.push((0,-1));
-                    }
-
-                    if loc.1 == 8 - 2 {
-                        // Forward 2 steps
-                        let new_loc = (loc.0 as i32 + 0, loc.1 as i32 - 2);
-                        if is_out_of_bounds(new_loc) { return moves; }
-                        let new_loc = (new_loc.0 as usize, new_loc.1 as usize);
-                        if !game.board[new_loc].is_some_and(|p| p.color == game.cur_color) {
-                            moves.push((0,-2));
-                        }
-                    } else {
-                        for dir in [(-1,-1),(1,-1)] {
-                            let new_loc = (loc.0 as i32 + dir.0, loc.1 as i32 + dir.1);
-                            if is_out_of_bounds(new_loc) { continue; }
-                            let new_loc = (new_loc.0 as usize, new_loc.1 as usize);
-                            if game.cur_en_passant == Some(new_loc) {
-                                moves.push(dir);
-                                break;
-                            }
-                        }
-                    }
-                    for dir in [(-1,-1),(1,-1)] {
-                        let new_loc = (loc.0 as i32 + dir.0, loc.1 as i32 + dir.1);
-                        if is_out_of_bounds(new_loc) { continue; }
-                        let new_loc = (new_loc.0 as usize, new_loc.1 as usize);
-                        if game.board[new_loc].is_some_and(|p| p.color != game.cur_color) {
-                            moves.push(dir);
-                        }
-                    }
-                },
-            }
-        }
-        PieceKind::Knight => {
-            let mut rvec = Vec::new();
-            for tile in [(-1,-2),(1,-2),(2,-1),(2,1),(1,2),(-1,2),(-2,1),(-2,-1)] {
-                let new_loc = (loc.0 as i32 + tile.0, loc.1 as i32 + tile.1);
-                if !is_out_of_bounds(new_loc) && (board[new_loc].is_none()
-                    || board[new_loc].unwrap().color != piece.color) {
-                    rvec.push(tile);
-                }
-            }
-            moves = rvec;
-        }
-        PieceKind::Bishop => {
-            let mut rvec = Vec::new();
-            for diag in [(-1,-1),(1,-1),(1,1),(-1,1)] {
-                let mut change = diag;
-                let mut new_loc = (loc.0 as i32 + change.0, loc.1 as i32 + change.1);
-                while !is_out_of_bounds(new_loc) && board[new_loc].is_none() {
-                    rvec.push(change);
-                    change = (change.0 + diag.0, change.1 + diag.1);
-                    new_loc = (loc.0 as i32 + change.0, loc.1 as i32 + change.1);
-                }
-                // If it is the opposite color
-                if !is_out_of_bounds(new_loc) {
-                    if let Some(x) = &board[new_loc] {
-                        if x.color != piece.color {
-                            rvec.push(change);
-                        }
-                    }
-                }
-            }
-            moves = rvec;
-        }
-        PieceKind::Rook => {
-            let mut rvec = Vec::new();
-            for dir in [(-1,0),(0,-1),(1,0),(0,1)] {
-                let mut change = dir;
-                let mut new_loc = (loc.0 as i32 + change.0, loc.1 as i32 + change.1); 
-                while !is_out_of_bounds(new_loc) && board[new_loc].is_none() {
-                    rvec.push(change);
-                    change = (change.0 + dir.0, change.1 + dir.1);
-                    new_loc = (loc.0 as i32 + change.0, loc.1 as i32 + change.1);
-                }
-                // If it is the opposite color
-                if !is_out_of_bounds(new_loc) {
-                    if let Some(x) = &board[new_loc] {
-                        if x.color != piece.color {
-                            rvec.push(change);
-                        }
-                    }
-                }
-            }
-            moves = rvec;
-        }
-        PieceKind::Queen => {
-            let mut rvec = Vec::new();
-            // Basically both a rook and a bishop
-            for diag in [(-1,-1),(1,-1),(1,1),(-1,1)] {
-                let mut change = diag;
-                let mut new_loc = (loc.0 as i32 + change.0, loc.1 as i32 + change.1);
-                while !is_out_of_bounds(new_loc) && board[new_loc].is_none() {
-                    rvec.push(change);
-                    change = (change.0 + diag.0, change.1 + diag.1);
-                    new_loc = (loc.0 as i32 + change.0, loc.1 as i32 + change.1);
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut opts = Options::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--white-name" | "--black-name" => {
+                let name = match args.next() {
+                    Some(x) => x.clone(),
+                    None => return Err(format!("{} expects a name", arg)),
+                };
+                if arg == "--white-name" {
+                    opts.players.white = name;
+                } else {
+                    opts.players.black = name;
                 }
-                // If it is the opposite color
-                if !is_out_of_bounds(new_loc) {
-                    if let Some(x) = &board[new_loc] {
-                        if x.color != piece.color {
-                            rvec.push(change);
-                        }
-                    }
-                }
-            }
-            for dir in [(-1,0),(0,-1),(1,0),(0,1)] {
-                let mut change = dir;
-                let mut new_loc = (loc.0 as i32 + change.0, loc.1 as i32 + change.1); 
-                while !is_out_of_bounds(new_loc) && board[new_loc].is_none() {
-                    rvec.push(change);
-                    change = (change.0 + dir.0, change.1 + dir.1);
-                    new_loc = (loc.0 as i32 + change.0, loc.1 as i32 + change.1);
-                }
-                // If it is the opposite color
-                if !is_out_of_bounds(new_loc) {
-                    if let Some(x) = &board[new_loc] {
-                        if x.color != piece.color {
-                            rvec.push(change);
-                        }
-                    }
+            },
+            "--no-en-passant" => opts.rules.en_passant = false,
+            "--no-castling" => opts.rules.castling = false,
+            "--quiet" => opts.quiet = true,
+            "--no-prompt" => opts.no_prompt = true,
+            "--board-style" => {
+                let style = args.next().and_then(|x| RenderOpts::preset(x));
+                match style {
+                    // Keep --unicode and --flip whichever order the flags come in
+                    Some(x) => opts.render = RenderOpts { unicode: x.unicode || opts.render.unicode, flipped: opts.render.flipped, ..x },
+                    None => return Err(format!("{} expects one of plain, shaded, bordered, unicode", arg)),
                 }
-            }
-            moves = rvec;
-        }
-        PieceKind::King => {
-            let mut rvec = Vec::new();
-            for tile in [(-1,0),(-1,-1),(0,-1),(1,-1),(1,0),(1,1),(0,1),(-1,1)] {
-                let new_loc = (loc.0 as i32 + tile.0, loc.1 as i32 + tile.1);
-                if !is_out_of_bounds(new_loc) && (board[new_loc].is_none()
-                    || board[new_loc].unwrap().color != piece.color) {
-                    rvec.push(tile);
+            },
+            "--unicode" => opts.render.unicode = true,
+            "--flip" => opts.render.flipped = true,
+            "--follow-turn" => opts.follow_turn = true,
+            "--moves" => match args.next() {
+                Some(x) => opts.moves = Some(x.clone()),
+                None => return Err(format!("{} expects a move list like \"e2e4 e7e5\"", arg)),
+            },
+            "--fen" => match args.next() {
+                Some(x) => opts.fen = Some(x.clone()),
+                None => return Err(format!("{} expects a position in FEN", arg)),
+            },
+            "--ai" => match args.next().map(|x| x.as_str()) {
+                Some("white") => opts.ai = Some(Color::White),
+                Some("black") => opts.ai = Some(Color::Black),
+                _ => return Err(format!("{} expects white or black", arg)),
+            },
+            "--depth" => match args.next().and_then(|x| x.parse().ok()) {
+                Some(depth) => opts.depth = Some(depth),
+                None => return Err(format!("{} expects a number of moves to look ahead", arg)),
+            },
+            "--random-opening" => {
+                let seed = args.next().and_then(|x| x.parse().ok());
+                let plies = args.next().and_then(|x| x.parse().ok());
+                match (seed, plies) {
+                    (Some(seed), Some(plies)) => opts.random_opening = Some((seed, plies)),
+                    _ => return Err(format!("{} expects a seed and a number of plies", arg)),
                 }
-
-            }
-            moves = rvec;
+            },
+            _ => return Err(format!("Unknown argument {}", arg)),
         }
     }
-
-    // Self check code;; not working
-    if game.is_checked {
-        moves.into_iter().filter(|mv| {
-            let new_loc = (loc.0 as i32 + mv.0, loc.1 as i32 + mv.1); 
-            if is_out_of_bounds(new_loc) { return false; }
-            let new_loc = (new_loc.0 as usize, new_loc.1 as usize);
-            let mut new_game = *game;
-            new_game.is_checked = false;
-            new_game.board[new_loc] = game.board[loc];
-            new_game.board[loc] = None;
-            // debug: show_moves(new_loc, loc, &new_game);
-
-            new_game.cur_color = match game.board[loc].unwrap().color {
-                Color::Black => Color::White,
-                Color::White => Color::Black,
-            };
-
-            if is_checked(&mut new_game) {
-                false
-            } else {
-                true
-            }
-        }).collect()
-    } else {
-        moves
+    if opts.fen.is_some() && opts.moves.is_some() {
+        return Err("--fen and --moves cannot be used together".to_string());
     }
+    Ok(opts)
 }
 
-#[derive(Copy, Clone, Debug)]
-struct Piece {
-    kind: PieceKind,
-    color: Color,
-}
-
-impl ToString for Piece {
-    fn to_string(&self) -> String {
-        self.color.color(&self.kind.to_string())
+fn print_capture_summary(game: &Game, players: &Players, winner: Option<Color>) {
+    info!("\x1b[34;1mMoves: \x1b[33;1m{}\x1b[0m", game.pgn_movetext());
+    for color in [Color::White, Color::Black] {
+        let total: u8 = game.captures_by[color as usize].iter().sum();
+        info!("\x1b[35;1m{}\x1b[34;1m captured \x1b[33;1m{}\x1b[34;1m pieces worth \x1b[33;1m{}\x1b[34;1m points\x1b[0m",
+            players.name(color),
+            total,
+            game.material_taken[color as usize],
+        );
     }
-}
 
-impl Piece {
-    fn is_valid_move(self: &Self, from: Location, to: Location, game: &Game) -> bool {
-        // Check if the location you want to go to, is not occupied by your own piece
-        if let Some(el) = &game.board[to] {
-            if el.color == self.color {
-                println!("\x1b[31;1mMoveError\x1b[0m: \x1b[34;1mCannot move to occupied tile \x1b[33;1m{}\x1b[0m\x1b[0m", loc2move(to));
-                return false;
+    let mut best: Option<(Piece, u8)> = None;
+    for color in [Color::White, Color::Black] {
+        for kind in PIECE_KINDS {
+            let count = game.captures_by[color as usize][kind as usize];
+            if count > best.map_or(0, |(_, n)| n) {
+                best = Some((Piece { kind, color }, count));
             }
         }
-
-        let diff = (to.0 as i32 - from.0 as i32, to.1 as i32 - from.1 as i32);
-        let moves = get_moves(from, game);
-        moves.contains(&diff)
     }
-}
+    if let Some((piece, count)) = best {
+        info!("\x1b[34;1mMost captures: \x1b[0m{} {}\x1b[34;1m with \x1b[33;1m{}\x1b[0m", piece.color, piece, count);
+    }
 
-static BOARD: Board = Board([
-    [ Some(Piece { kind: PieceKind::Rook, color: Color::Black }), Some(Piece { kind: PieceKind::Knight, color: Color::Black }), Some(Piece { kind: PieceKind::Bishop, color: Color::Black }), Some(Piece { kind: PieceKind::Queen, color: Color::Black }), Some(Piece { kind: PieceKind::King, color: Color::Black }), Some(Piece { kind: PieceKind::Bishop, color: Color::Black }), Some(Piece { kind: PieceKind::Knight, color: Color::Black }), Some(Piece { kind: PieceKind::Rook, color: Color::Black }) ],
-    [ Some(Piece { kind: PieceKind::Pawn, color: Color::Black }), Some(Piece { kind: PieceKind::Pawn, color: Color::Black }), Some(Piece { kind: PieceKind::Pawn, color: Color::Black }), Some(Piece { kind: PieceKind::Pawn, color: Color::Black }), Some(Piece { kind: PieceKind::Pawn, color: Color::Black }), Some(Piece { kind: PieceKind::Pawn, color: Color::Black }), Some(Piece { kind: PieceKind::Pawn, color: Color::Black }), Some(Piece { kind: PieceKind::Pawn, color: Color::Black }), ],
-    [ None, None, None, None, None, None, None, None ],
-    [ None, None, None, None, None, None, None, None ],
-    [ None, None, None, None, None, None, None, None ],
-    [ None, None, None, None, None, None, None, None ],
-    [ Some(Piece { kind: PieceKind::Pawn, color: Color::White }), Some(Piece { kind: PieceKind::Pawn, color: Color::White }), Some(Piece { kind: PieceKind::Pawn, color: Color::White }), Some(Piece { kind: PieceKind::Pawn, color: Color::White }), Some(Piece { kind: PieceKind::Pawn, color: Color::White }), Some(Piece { kind: PieceKind::Pawn, color: Color::White }), Some(Piece { kind: PieceKind::Pawn, color: Color::White }), Some(Piece { kind: PieceKind::Pawn, color: Color::White }), ],
-    [ Some(Piece { kind: PieceKind::Rook, color: Color::White }), Some(Piece { kind: PieceKind::Knight, color: Color::White }), Some(Piece { kind: PieceKind::Bishop, color: Color::White }), Some(Piece { kind: PieceKind::Queen, color: Color::White }), Some(Piece { kind: PieceKind::King, color: Color::White }), Some(Piece { kind: PieceKind::Bishop, color: Color::White }), Some(Piece { kind: PieceKind::Knight, color: Color::White }), Some(Piece { kind: PieceKind::Rook, color: Color::White }) ],
-]);
-
-fn print_board(board: &Board) {
-    for (i, row) in board.0.iter().enumerate() {
-        print!("{} ", 8 - i);
-        for el in row {
-            match el {
-                Some(x) => {
-                    print!("{}", x.to_string());
-                },
-                None => print!(" "),
-            }
+    // Rough guess: a winner clearly ahead on captured material won on material
+    if let Some(winner) = winner {
+        let loser = match winner {
+            Color::Black => Color::White,
+            Color::White => Color::Black,
+        };
+        if game.material_taken[winner as usize] >= game.material_taken[loser as usize] + 3 {
+            info!("\x1b[34;1mThe game was decided by material\x1b[0m");
+        } else {
+            info!("\x1b[34;1mThe game was decided by tactics\x1b[0m");
         }
-        println!();
-    }
-    print!("  ");
-    for i in 0..8 {
-        print!("{}", ('a' as u8 + i) as char);
     }
-    println!();
 }
 
-// Why does this take 2 locations
-fn show_moves(from: Location, to: Location, game: &Game) {
-    let board = &game.board;
-    let possible_moves = get_moves(from, game);
-    if possible_moves.is_empty() {
-        println!("\x1b[34;1mThere are no available moves for \x1b[0m{}\x1b[34;1m at \x1b[35;1m{}\x1b[0m",
-            board[from].unwrap().to_string(),
+// Plays the move if it is legal, true then. Says why not otherwise.
+fn play_move(from: Location, to: Location, promotion: Option<PieceKind>, game: &mut Game, players: &Players, render: &RenderOpts) -> bool {
+    let mover = game.board[from];
+
+    // Move the piece if it may, which also hands the turn to the other player
+    match apply_move(game, from, to, promotion) {
+        Ok(effects) => announce_move(mover.unwrap(), from, to, &effects),
+        Err(MoveError::NoPiece) => {
+            println!("\x1b[31;1mLocationError\x1b[0m: \x1b[34;1mLocation \x1b[33;1m{}\x1b[34;1m Has no piece on it\x1b[0m", loc2move(from));
+            return false;
+        },
+        Err(MoveError::WrongTurn) => {
+            println!("\x1b[31;1mPlayerError\x1b[0m: \x1b[35;1m{}\x1b[34;1m Is playing right now, thus cannot move \x1b[35;1m{}\x1b[34;1m Piece\x1b[0m",
+                players.name(game.cur_color),
+                mover.unwrap().color,
+            );
+            return false;
+        },
+        Err(MoveError::OccupiedByOwn) => {
+            println!("\x1b[31;1mMoveError\x1b[0m: \x1b[34;1mCannot move to occupied tile \x1b[33;1m{}\x1b[0m", loc2move(to));
+            return false;
+        },
+        Err(MoveError::NotReachable) => {
+            println!("\x1b[31;1mInvalidMoveError\x1b[0m:\x1b[34;1m Displaying tried move, and all possible moves from this piece\x1b[0m.");
+            show_moves(from, to, game, render);
+            return false;
+        },
+        Err(MoveError::WouldLeaveKingInCheck) => {
+            println!("\x1b[31;1mCheckError\x1b[0m:\x1b[34;1m That would leave your king in check, displaying all possible moves from this piece\x1b[0m.");
+            show_moves(from, to, game, render);
+            return false;
+        },
+        Err(MoveError::BadPromotion) => {
+            println!("\x1b[31;1mPromotionError\x1b[0m: \x1b[34;1mA pawn can only promote to \x1b[33;1mQ\x1b[34;1m, \x1b[33;1mR\x1b[34;1m, \x1b[33;1mB\x1b[34;1m or \x1b[33;1mN\x1b[0m");
+            return false;
+        },
+    }
+
+    // The main loop announces the end of the game
+    if game.is_terminal() {
+        return true;
+    }
+    is_checked(game);
+    if let Some((from, to)) = game.checking_move {
+        info!("\x1b[35;1m{}\x1b[34;1m is checked after \x1b[0m{}\x1b[34;1m moved from \x1b[33;1m{}\x1b[34;1m to \x1b[33;1m{}\x1b[0m",
+            players.name(game.cur_color),
+            game.board[to].unwrap(),
             loc2move(from),
+            loc2move(to),
         );
     }
+    true
+}
 
-    for (i, row) in board.0.iter().enumerate() {
-        print!("{} ", 8 - i);
-        for (j, el) in row.iter().enumerate() {
-            if (j, i) == from {
-                print!("\x1b[34;1m{}\x1b[0m", el.unwrap().kind.to_string());
-            } else {
-                match el {
-                    Some(x) => {
-                        if possible_moves.contains(&(j as i32 - from.0 as i32, i as i32 - from.1 as i32)) {
-                            print!("\x1b[36;1m{}\x1b[0m", x.kind.to_string());
-                        } else if (j, i) == to {
-                            print!("\x1b[31;1m{}\x1b[0m", x.kind.to_string());
-                        } else {
-                            print!("{}", x.to_string());
-                        }
-                    },
-                    None => {
-                        if possible_moves.contains(&(j as i32 - from.0 as i32, i as i32 - from.1 as i32)) {
-                            print!("\x1b[34;1m*\x1b[0m");
-                        } else if (j, i) == to {
-                            print!("\x1b[31;1mx\x1b[0m");
-                        } else {
-                            print!(" ")
-                        }
-                    },
-                }
-            }
+// Tells the players what `mover` just did
+fn announce_move(mover: Piece, from: Location, to: Location, effects: &MoveEffects) {
+    if let Some((taken, at)) = effects.captured {
+        if at == to {
+            info!("{}\x1b[36;1m has been captured by \x1b[0m{} \x1b[36;1mat \x1b[33;1m{}\x1b[0m", taken, mover, loc2move(at));
+        } else {
+            info!("{}\x1b[36;1m has been captured en passant by \x1b[0m{} \x1b[36;1mat \x1b[33;1m{}\x1b[0m", taken, mover, loc2move(at));
         }
-        println!();
     }
-    print!("  ");
-    for i in 0..8 {
-        print!("{}", ('a' as u8 + i) as char);
+    info!("{}\x1b[36;1m was moved from \x1b[33;1m{}\x1b[36;1m to \x1b[33;1m{}\x1b[0m", mover, loc2move(from), loc2move(to));
+    if let Some((rook_from, rook_to)) = effects.castled {
+        info!("{}\x1b[36;1m castled, the rook was moved from \x1b[33;1m{}\x1b[36;1m to \x1b[33;1m{}\x1b[0m", mover, loc2move(rook_from), loc2move(rook_to));
+    }
+    if let Some(kind) = effects.promoted {
+        info!("{}\x1b[36;1m on \x1b[33;1m{}\x1b[36;1m was promoted to \x1b[0m{}", mover, loc2move(to), Piece { kind, color: mover.color });
     }
-    println!();
 }
 
-fn dist(a: Location, b: Location) -> usize {
-    a.1.abs_diff(b.1) + a.0.abs_diff(b.0)
+// Announces how the game ended
+fn report_result(game: &Game, players: &Players) {
+    match game.result() {
+        GameResult::Checkmate(winner) => {
+            println!("\x1b[35;1m{}\x1b[34;1m wins by checkmate\x1b[0m", players.name(winner));
+            print_capture_summary(game, players, Some(winner));
+        },
+        GameResult::Stalemate => {
+            println!("Stalemate");
+            print_capture_summary(game, players, None);
+        },
+        GameResult::FiftyMoves => {
+            println!("\x1b[34;1mDraw by the fifty-move rule, no capture or pawn move in the last 50 moves\x1b[0m");
+            print_capture_summary(game, players, None);
+        },
+        GameResult::InsufficientMaterial => {
+            println!("\x1b[34;1mDraw, neither side has enough material left to mate\x1b[0m");
+            print_capture_summary(game, players, None);
+        },
+        GameResult::Repetition => {
+            println!("\x1b[34;1mDraw by threefold repetition\x1b[0m");
+            print_capture_summary(game, players, None);
+        },
+        GameResult::Ongoing => {},
+    }
 }
 
-fn move_to(from: Location, to: Location, game: &mut Game) {
-    let board = &mut game.board;
+// Longer lines are rejected whole, rather than played token by token
+const MAX_INPUT_LEN: usize = 512;
 
-    game.is_checked = false;
+// Textbook endgames to practice with the `endgame` command
+const ENDGAMES: [(&str, &str); 5] = [
+    ("KQvK", "8/8/8/4k3/8/8/8/3QK3 w - - 0 1"),
+    ("KRvK", "8/8/8/4k3/8/8/8/R3K3 w - - 0 1"),
+    ("KBNvK", "8/8/8/4k3/8/8/8/2B1KN2 w - - 0 1"),
+    ("Lucena", "1K1k4/1P6/8/8/8/8/r7/2R5 w - - 0 1"),
+    ("Philidor", "4k3/8/r7/4PK2/8/8/8/4R3 b - - 0 1"),
+];
 
-    if game.cur_en_passant.is_some() {
-        game.cur_en_passant = None;
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let opts = match parse_args(&args) {
+        Ok(x) => x,
+        Err(e) => {
+            println!("\x1b[31;1mArgumentError\x1b[0m: \x1b[34;1m{}\x1b[0m", e);
+            return;
+        },
+    };
+    let players = &opts.players;
+    set_quiet(opts.quiet);
+    let mut render = opts.render;
+
+    let mut game = Game::new().with_rules(opts.rules);
+
+    // Start from a given position or a shared move list instead of the opening position
+    if let Some(fen) = &opts.fen {
+        game = match Game::from_fen(fen) {
+            Ok(x) => x.with_rules(opts.rules),
+            Err(e) => {
+                println!("\x1b[31;1mFenError\x1b[0m: \x1b[34;1m{}\x1b[0m", e);
+                return;
+            },
+        };
     }
-
-    if board[from].unwrap().kind == PieceKind::Pawn && dist(from, to) == 2 {
-        game.cur_en_passant = Some(to);
+    if let Some(moves) = &opts.moves {
+        game = match apply_move_string(moves, opts.rules) {
+            Ok(x) => x,
+            Err(e) => {
+                println!("\x1b[31;1mMoveListError\x1b[0m: \x1b[34;1m{}\x1b[0m", e);
+                return;
+            },
+        };
     }
 
-    if board[to].is_some() {
-        println!("{}\x1b[36;1m has been captured by \x1b[0m{} \x1b[36;1mat \x1b[33;1m{}\x1b[0m",
-            board[to].unwrap().to_string(),
-            board[from].unwrap().to_string(),
-            loc2move(to),
-        );
+    // Play random opening moves before handing over to the players
+    if let Some((seed, plies)) = opts.random_opening {
+        let mut rng = XorShift::new(seed);
+        for _ in 0..plies {
+            if game.is_terminal() {
+                break;
+            }
+            let Some((from, to)) = ai::random_move(&game, game.cur_color, &mut rng) else {
+                break;
+            };
+            play_move(from, to, None, &mut game, players, &render);
+        }
     }
 
-    board[to] = board[from];
-    board[from] = None;
-    println!("{}\x1b[36;1m was moved from \x1b[33;1m{}\x1b[36;1m to \x1b[33;1m{}\x1b[0m", board[to].unwrap().to_string(), loc2move(from), loc2move(to));
-}
-
-fn move2loc(input: &str) -> (i32, i32) {
-    (
-        input.chars().nth(0).unwrap() as i32 - 'a' as i32,
-        8 - (input.chars().nth(1).unwrap() as i32 - '0' as i32),
-    )
-}
+    // Piped input gets the prompt and board only when asked for with `board`
+    let prompt = !opts.no_prompt && std::io::stdin().is_terminal();
 
-fn debugloc2move(loc: (i32, i32)) -> String {
-    format!("{}{}",
-        ('a' as u8 + loc.0 as u8) as char,
-        8 - loc.1,
-    )
-}
+    let seed = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(1, |x| x.as_nanos() as u64);
+    let mut rng = XorShift::new(seed);
 
-fn loc2move(loc: Location) -> String {
-    format!("{}{}",
-        ('a' as u8 + loc.0 as u8) as char,
-        8 - loc.1,
-    )
-}
+    // The side the board was last turned towards, so `flip` still works with --follow-turn
+    let mut facing = Color::White;
 
-fn is_out_of_bounds(loc: (i32, i32)) -> bool {
-    loc.0 < 0 || loc.1 < 0 || loc.0 > 7 || loc.1 > 7
-}
+    // A loaded position can already be over, with no move left to ask for
+    while !game.is_terminal() {
+        if opts.follow_turn && game.cur_color != facing {
+            render.flipped = !render.flipped;
+            facing = game.cur_color;
+        }
 
-fn get_king_location(board: &Board, color: Color) -> Location {
-    for i in 0..8 {
-        for j in 0..8 {
-            if let Some(piece) = board[(i as usize, j as usize)] {
-                if piece.kind == PieceKind::King && piece.color == color {
-                    return (i as usize, j as usize);
-                }
+        // The computer's turn, shown even when quiet since the player has to know what it did
+        if opts.ai == Some(game.cur_color) {
+            let choice = match opts.depth {
+                Some(depth) => ai::best_move(&game, game.cur_color, depth),
+                None => ai::random_move(&game, game.cur_color, &mut rng),
+            };
+            let Some((from, to)) = choice else {
+                break;
+            };
+            if is_quiet() {
+                println!("\x1b[35;1m{}\x1b[34;1m plays \x1b[33;1m{}{}\x1b[0m", players.name(game.cur_color), loc2move(from), loc2move(to));
             }
+            play_move(from, to, None, &mut game, players, &render);
+            continue;
         }
-    }
-    panic!("King already dead?");
-}
 
-fn is_checked(game: &mut Game) -> bool {
-    let loc   = get_king_location(&game.board, game.cur_color);
-    let piece = game.board[loc];
-    for piece_kind in [PieceKind::Pawn, PieceKind::Rook, PieceKind::Bishop, PieceKind::Knight, PieceKind::Queen] {
-        game.board[loc] = Some(Piece { kind: piece_kind, color: game.cur_color });
-        let possible_moves = get_moves(loc, &game);
-        for (dx, dy) in possible_moves {
-            let new_loc = (loc.0 as i32 + dx, loc.1 as i32 + dy);
-            if is_out_of_bounds(new_loc) { continue; }
-            let new_loc = (new_loc.0 as usize, new_loc.1 as usize);
-            if let Some(target_piece) = game.board[new_loc] {
-                if target_piece.kind == piece_kind && target_piece.color != game.cur_color {
-                    game.board[loc] = piece;
-                    game.is_checked = true;
-                    return true;
-                }
-            }
+        if prompt && !is_quiet() {
+            println!("\x1b[35;1m{}\x1b[34;1m is playing right now.\x1b[0m", players.name(game.cur_color));
+            print_board(&game.board, game.last_move(), &render);
         }
-    }
-    game.board[loc] = piece;
-    false
-}
 
-fn has_no_valid_moves(game: &Game, color: Color) -> bool {
-    game.board.0.into_iter().enumerate().map(|(i, row)| row.into_iter().enumerate().map(|(j, cell)| {
-        match cell {
-            Some(x) => if x.color == color {
-                let loc = (j as usize, i as usize);
-                get_moves(loc, game).len()
-            } else {
-                0
+        // Get the input
+        let mut line = String::new();
+        match std::io::stdin().read_line(&mut line) {
+            // Stop at the end of piped input instead of spinning on empty lines
+            Ok(0) => return,
+            Ok(_) => {},
+            Err(_) => {
+                println!("\x1b[31;1mInputError\x1b[0m: \x1b[34;1mInput is not valid text\x1b[0m");
+                continue;
             },
-            None    => 0,
         }
-    }).count()).count() == 0
-}
-
-#[allow(dead_code)]
-impl Game {
-    fn can_castle_short(self: &Self, color: Color) -> bool {
-        let row_num = match color {
-            Color::Black => 0,
-            Color::White => 7,
-        };
+        if line.len() > MAX_INPUT_LEN {
+            println!("\x1b[31;1mInputError\x1b[0m: \x1b[34;1mInput is longer than \x1b[33;1m{}\x1b[34;1m characters, ignoring it\x1b[0m", MAX_INPUT_LEN);
+            continue;
+        }
+        let comm: Vec<_> = line.split_whitespace().collect();
 
-        self.board.0[row_num][4].is_some_and(|p| p.kind == PieceKind::King) &&
-        self.board.0[row_num][5].is_none() &&
-        self.board.0[row_num][6].is_none() &&
-        self.board.0[row_num][7].is_some_and(|p| p.kind == PieceKind::Rook) && !self.is_checked
-    }
+        // Special commands
+        if comm.len() == 2 && comm[0] == "help" {
+            let Some(loc) = parse_square(comm[1]) else {
+                println!("\x1b[31;1mInvalidLocationError\x1b[0m: \x1b[34;1mSupplied \x1b[33;1m{}\x1b[34;1m which is not a square on the board\x1b[0m", comm[1]);
+                continue;
+            };
+            match game.board[loc] {
+                None => println!("\x1b[31;1mLocationError\x1b[0m: \x1b[34;1mLocation \x1b[33;1m{}\x1b[34;1m Has no piece on it\x1b[0m", loc2move(loc)),
+                // The opponent's pieces show what they threaten, not moves you could make
+                Some(x) if x.color != game.cur_color => {
+                    println!("\x1b[34;1mSquares threatened by \x1b[0m{}\x1b[34;1m at \x1b[35;1m{}\x1b[0m", x, loc2move(loc));
+                    print_marked_board(loc, (9, 9), &threats(loc, &game), &game, &render);
+                },
+                Some(_) => show_moves(loc, (9, 9), &game, &render),
+            }
+            continue;
+        }
 
-    fn can_castle_long(&self, color: Color) -> bool {
-        let row_num = match color {
-            Color::Black => 0,
-            Color::White => 7,
-        };
+        if comm.len() == 1 && comm[0] == "board" {
+            println!("\x1b[35;1m{}\x1b[34;1m is playing right now.\x1b[0m", players.name(game.cur_color));
+            print_board(&game.board, game.last_move(), &render);
+            continue;
+        }
 
-        self.board.0[row_num][4].is_some_and(|p| p.kind == PieceKind::King) &&
-        self.board.0[row_num][3].is_none() &&
-        self.board.0[row_num][2].is_none() &&
-        self.board.0[row_num][1].is_none() &&
-        self.board.0[row_num][0].is_some_and(|p| p.kind == PieceKind::Rook) && !self.is_checked
-    }
-}
+        if comm.len() == 1 && comm[0] == "reset" {
+            println!("\x1b[34;1mThrow away this game and start over? \x1b[33;1m(y/n)\x1b[0m");
+            let mut answer = String::new();
+            if std::io::stdin().read_line(&mut answer).is_ok() && matches!(answer.trim(), "y" | "yes") {
+                game = Game::new().with_rules(game.rules);
+                info!("\x1b[34;1mThe game has been reset\x1b[0m");
+            }
+            continue;
+        }
 
-fn main() {
-    let mut game = Game {
-        board: BOARD,
-        cur_color: Color::White,
-        cur_en_passant: None,
-        is_checked: false
-    };
+        if comm.len() == 2 && comm[0] == "endgame" {
+            match ENDGAMES.iter().find(|(name, _)| name.eq_ignore_ascii_case(comm[1])) {
+                Some((name, fen)) => {
+                    game = Game::from_fen(fen).unwrap().with_rules(game.rules);
+                    info!("\x1b[34;1mLoaded the \x1b[33;1m{}\x1b[34;1m endgame\x1b[0m", name);
+                },
+                None => {
+                    let names: Vec<_> = ENDGAMES.iter().map(|(name, _)| *name).collect();
+                    println!("\x1b[31;1mEndgameError\x1b[0m: \x1b[34;1mNo endgame called \x1b[33;1m{}\x1b[34;1m, try one of \x1b[33;1m{}\x1b[0m", comm[1], names.join(", "));
+                },
+            }
+            continue;
+        }
 
-    loop {
-        println!("\x1b[35;1m{}\x1b[34;1m is playing right now.\x1b[0m", game.cur_color.to_string());
-        print_board(&game.board);
+        if comm.len() == 1 && (comm[0] == "undo" || comm[0] == "redo") {
+            let step = |game: &mut Game| if comm[0] == "undo" { game.undo() } else { game.redo() };
+            let done = step(&mut game);
+            // Against the computer go on to the player's own turn, or it would just move again
+            if done && opts.ai == Some(game.cur_color) {
+                step(&mut game);
+            }
+            if done {
+                info!("\x1b[35;1m{}\x1b[34;1m is playing right now.\x1b[0m", players.name(game.cur_color));
+            } else {
+                println!("\x1b[31;1mHistoryError\x1b[0m: \x1b[34;1mThere is no move to {}\x1b[0m", comm[0]);
+            }
+            continue;
+        }
 
-        // Get the input
-        let mut line = String::new();
-        std::io::stdin().read_line(&mut line).unwrap();
-        let comm: Vec<_> = line.split(' ').collect();
+        if comm.len() == 1 && comm[0] == "flip" {
+            render.flipped = !render.flipped;
+            continue;
+        }
 
-        // Check if the supplied arguments are correct
-        if comm.len() != 2 {
-            println!("Incorrect input! Supplied: {}", line);
+        if comm.len() == 2 && comm[0] == "at" {
+            if parse_square(comm[1]).is_none() {
+                println!("\x1b[31;1mInvalidLocationError\x1b[0m: \x1b[34;1mSupplied \x1b[33;1m{}\x1b[34;1m which is not a square on the board\x1b[0m", comm[1]);
+                continue;
+            }
+            match piece_at_algebraic(&game, comm[1]) {
+                Some(x) => println!("{} {}\x1b[34;1m is on \x1b[33;1m{}\x1b[0m", x.color, x, comm[1]),
+                None => println!("\x1b[33;1m{}\x1b[34;1m is empty\x1b[0m", comm[1]),
+            }
             continue;
         }
 
-        // Special commands
-        if comm[0] == "help" {
-            let loc = move2loc(comm[1]);
-            if is_out_of_bounds(loc) {
-                println!("\x1b[31;1mInvalidLocationError\x1b[0m: \x1b[34;1mSupplied \x1b[33;1m{}\x1b[34;1m which is outside of the board\x1b[0m", debugloc2move(loc));
+        if comm.len() == 3 && comm[0] == "trade" {
+            let (from, to) = match (parse_square(comm[1]), parse_square(comm[2])) {
+                (Some(from), Some(to)) => (from, to),
+                _ => {
+                    println!("\x1b[31;1mInvalidLocationError\x1b[0m: \x1b[34;1mSupplied \x1b[33;1m{} \x1b[34;1mto \x1b[33;1m{}, \x1b[34;1mWhich is outside of the board\x1b[0m", comm[1], comm[2]);
+                    continue;
+                },
+            };
+            if !game.board[from].is_some_and(|p| p.color == game.cur_color) || !destinations(from, &game).contains(&to) {
+                println!("\x1b[31;1mInvalidMoveError\x1b[0m: \x1b[35;1m{}\x1b[34;1m cannot play \x1b[33;1m{} \x1b[34;1mto \x1b[33;1m{}\x1b[0m", players.name(game.cur_color), comm[1], comm[2]);
                 continue;
             }
-            show_moves((loc.0 as usize, loc.1 as usize), (9, 9), &game);
+            let outcome = see(&game, from, to);
+            println!("\x1b[34;1mAfter all trades on \x1b[33;1m{}\x1b[34;1m, \x1b[35;1m{}\x1b[34;1m is expected to {} \x1b[33;1m{}\x1b[34;1m points\x1b[0m",
+                comm[2],
+                players.name(game.cur_color),
+                if outcome < 0 { "lose" } else { "win" },
+                outcome.abs(),
+            );
             continue;
         }
 
-        // Check if the moves are on the board
-        let (from, to) = (move2loc(comm[0]), move2loc(comm[1]));
-        if is_out_of_bounds(from) || is_out_of_bounds(to) {
-            println!("\x1b[31;1mInvalidLocationError\x1b[0m: \x1b[34;1mSupplied \x1b[33;1m{} \x1b[34;1mto \x1b[33;1m{}, \x1b[34;1mWhich is outside of the board\x1b[0m", debugloc2move(from), debugloc2move(to));
+        if comm.len() == 1 && comm[0] == "checks" {
+            let checks: Vec<_> = all_legal_moves(&game, game.cur_color).into_iter()
+                .filter(|&(from, to)| gives_check(&game, from, to))
+                .map(|(from, to)| format!("{}{}", loc2move(from), loc2move(to)))
+                .collect();
+            if checks.is_empty() {
+                println!("\x1b[34;1mThere are no checking moves\x1b[0m");
+            } else {
+                println!("\x1b[34;1mChecking moves: \x1b[33;1m{}\x1b[0m", checks.join(" "));
+            }
             continue;
         }
-        let (from, to) = ((from.0 as usize, from.1 as usize), (to.0 as usize, to.1 as usize));
 
-        // Get the piece on the location if it exists
-        let piece = match &game.board[from] {
-            Some(x) => x,
-            None => {
-                println!("\x1b[31;1mLocationError\x1b[0m: \x1b[34;1mLocation \x1b[33;1m{}\x1b[34;1m Has no piece on it\x1b[0m", loc2move(from));
-                continue;
-            },
-        };
+        if comm.len() == 1 && comm[0] == "pgn" {
+            println!("[White \"{}\"]", players.white);
+            println!("[Black \"{}\"]", players.black);
+            println!();
+            println!("{}", game.pgn_movetext());
+            continue;
+        }
 
-        // Check if the piece is of your own color
-        if piece.color != game.cur_color {
-            println!("\x1b[31;1mPlayerError\x1b[0m: \x1b[35;1m{}\x1b[34;1m Is playing right now, thus cannot move \x1b[35;1m{}\x1b[34;1m Piece\x1b[0m",
-                game.cur_color.to_string(),
-                piece.color.to_string(),
-            );
+        if comm.len() == 1 && comm[0] == "fen" {
+            println!("\x1b[34;1mPosition: \x1b[33;1m{}\x1b[0m", game.to_fen());
             continue;
         }
 
-        // Maybe give back why it cant happen later, and not a boolean
-        if !piece.is_valid_move(from, to, &game) {
-            println!("\x1b[31;1mInvalidMoveError\x1b[0m:\x1b[34;1m Displaying tried move, and all possible moves from this piece\x1b[0m.");
-            show_moves(from, to, &game);
+        if comm.len() == 1 && comm[0] == "moves" {
+            println!("\x1b[34;1mMoves played: \x1b[33;1m{}\x1b[0m", moves_to_string(&game));
             continue;
         }
 
-        // If is king, move, then say who won
-        if let Some(piece) = game.board[to] {
-            if piece.kind == PieceKind::King {
-                move_to(from, to, &mut game);
-                println!("{} won", game.cur_color.to_string());
-                return;
+        if comm.len() == 2 && comm[0] == "perft" {
+            match comm[1].parse() {
+                Ok(depth) => println!("\x1b[34;1mPositions after \x1b[33;1m{}\x1b[34;1m moves: \x1b[33;1m{}\x1b[0m", depth, perft(&game, game.cur_color, depth)),
+                Err(_) => println!("\x1b[31;1mArgumentError\x1b[0m: \x1b[34;1mperft expects a depth, like \x1b[33;1mperft 3\x1b[0m"),
             }
+            continue;
         }
 
-        // Move the piece at last
-        move_to(from, to, &mut game);
+        if comm.len() == 1 && comm[0] == "count" {
+            println!("\x1b[35;1m{}\x1b[34;1m has \x1b[33;1m{}\x1b[34;1m possible moves\x1b[0m", players.name(game.cur_color), all_legal_moves(&game, game.cur_color).len());
+            continue;
+        }
 
-        // Change the player that is playing
-        game.cur_color = match game.cur_color {
-            Color::Black => Color::White,
-            Color::White => Color::Black,
+        // Several joined moves like `e2e4 e7e5 g1f3`, played until one is rejected
+        let is_joined = |mv: &str| (mv.len() == 4 || mv.len() == 5)
+            && mv.get(..2).and_then(parse_square).is_some()
+            && mv.get(2..4).and_then(parse_square).is_some();
+        if !comm.is_empty() && comm.iter().all(|mv| is_joined(mv)) {
+            for mv in comm {
+                if game.is_terminal() {
+                    break;
+                }
+                let promotion = match &mv[4..] {
+                    "" => None,
+                    x => match parse_promotion(x) {
+                        Some(kind) => Some(kind),
+                        None => {
+                            println!("\x1b[31;1mPromotionError\x1b[0m: \x1b[34;1mA pawn can only promote to \x1b[33;1mQ\x1b[34;1m, \x1b[33;1mR\x1b[34;1m, \x1b[33;1mB\x1b[34;1m or \x1b[33;1mN\x1b[34;1m, not \x1b[33;1m{}\x1b[0m", x);
+                            break;
+                        },
+                    },
+                };
+                let (from, to) = (parse_square(&mv[..2]).unwrap(), parse_square(&mv[2..4]).unwrap());
+                if !play_move(from, to, promotion, &mut game, players, &render) {
+                    break;
+                }
+            }
+            continue;
+        }
+
+        // A single move in algebraic notation, like `Nf3` or `O-O`
+        if comm.len() == 1 {
+            let Some((from, to, promotion)) = parse_san(comm[0], &game, game.cur_color) else {
+                println!("\x1b[31;1mInvalidMoveError\x1b[0m: \x1b[33;1m{}\x1b[34;1m is not a legal move, if two pieces can make it add the file or rank it comes from\x1b[0m", comm[0]);
+                continue;
+            };
+            play_move(from, to, promotion, &mut game, players, &render);
+            continue;
+        }
+
+        // Check if the supplied arguments are correct, a third one picks the promotion piece
+        if comm.len() != 2 && comm.len() != 3 {
+            println!("Incorrect input! Supplied: {}", line);
+            continue;
+        }
+
+        let promotion = match comm.get(2) {
+            None => None,
+            Some(x) => match parse_promotion(x) {
+                Some(kind) => Some(kind),
+                None => {
+                    println!("\x1b[31;1mPromotionError\x1b[0m: \x1b[34;1mA pawn can only promote to \x1b[33;1mQ\x1b[34;1m, \x1b[33;1mR\x1b[34;1m, \x1b[33;1mB\x1b[34;1m or \x1b[33;1mN\x1b[34;1m, not \x1b[33;1m{}\x1b[0m", x);
+                    continue;
+                },
+            },
         };
 
-        if is_checked(&mut game) {
-            if has_no_valid_moves(&game, game.cur_color) {
-                println!("Winner");
-                return;
-            }
-            println!("{} is checked", game.cur_color.to_string());
-        } else if has_no_valid_moves(&game, game.cur_color) {
-            println!("Stalemate");
-            return;
+        let (Some(from), Some(to)) = (parse_square(comm[0]), parse_square(comm[1])) else {
+            println!("\x1b[31;1mInvalidLocationError\x1b[0m: \x1b[34;1mSupplied \x1b[33;1m{} \x1b[34;1mto \x1b[33;1m{}, \x1b[34;1mWhich is outside of the board\x1b[0m", comm[0], comm[1]);
+            continue;
+        };
+        play_move(from, to, promotion, &mut game, players, &render);
+    }
+    report_result(&game, players);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn endgames_are_valid_positions() {
+        for (_, fen) in ENDGAMES {
+            let game = Game::from_fen(fen).unwrap();
+            assert_eq!(game.result(), GameResult::Ongoing);
         }
     }
 }
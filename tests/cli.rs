@@ -0,0 +1,106 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// Runs the game with `args` on piped `input`, giving back what it printed without the colors
+fn run(args: &[&str], input: &str) -> String {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_chess"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    let printed = String::from_utf8(output.stdout).unwrap();
+    let mut out = String::new();
+    let mut chars = printed.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            chars.by_ref().find(|&c| c == 'm');
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[test]
+fn overlong_line_is_rejected_once() {
+    let input = format!("{}\ne2e4\n", "e2e4 ".repeat(2048));
+    let out = run(&[], &input);
+    assert_eq!(out.matches("InputError").count(), 1);
+    assert_eq!(out.matches("was moved from e2 to e4").count(), 1);
+}
+
+#[test]
+fn squares_off_the_board_are_rejected() {
+    let out = run(&[], "a b\nhelp e\ne2 e4\n");
+    assert_eq!(out.matches("InvalidLocationError").count(), 2);
+    assert!(out.contains("was moved from e2 to e4"));
+}
+
+#[test]
+fn player_names_in_the_prompt_and_pgn() {
+    let out = run(&["--white-name", "Alice", "--black-name", "Bob"], "e2e4\nboard\npgn\n");
+    assert!(out.contains("Bob is playing right now."));
+    assert!(out.contains("[White \"Alice\"]\n[Black \"Bob\"]\n\n1. e4\n"));
+}
+
+#[test]
+fn several_moves_on_one_line() {
+    let out = run(&[], "e2e4 e7e5 g1f3\nfen\n");
+    assert_eq!(out.matches(" was moved from ").count(), 3);
+    assert!(out.contains("Position: rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"));
+
+    // The line stops at the first move that can't be played
+    let out = run(&[], "e2e4 e2e4 g1f3\nfen\n");
+    assert_eq!(out.matches(" was moved from ").count(), 1);
+    assert!(out.contains("Position: rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"));
+}
+
+#[test]
+fn quiet_game_prints_only_the_result() {
+    let out = run(&["--quiet"], "f2f3 e7e5 g2g4 d8h4\n");
+    assert_eq!(out, "Black wins by checkmate\n");
+}
+
+#[test]
+fn capture_summary_at_the_end() {
+    let out = run(&[], "f2f4 e7e5 f4e5 d7d6 e5d6 f8d6 g2g4 d8h4\n");
+    let summary = out.split("Black wins by checkmate\n").nth(1).unwrap();
+    assert_eq!(summary, "Moves: 1. f4 e5 2. fxe5 d6 3. exd6 Bxd6 4. g4 Qh4#\n\
+        White captured 2 pieces worth 2 points\n\
+        Black captured 1 pieces worth 1 points\n\
+        Most captures: White P with 2\n\
+        The game was decided by tactics\n");
+}
+
+#[test]
+fn piped_input_gets_no_prompt() {
+    let out = run(&[], "e2e4\ne7e5\ng1f3\n");
+    assert!(!out.contains("is playing right now"));
+    // Until the board is asked for
+    let out = run(&["--no-prompt"], "e2e4\nboard\ne7e5\n");
+    assert_eq!(out.matches("is playing right now").count(), 1);
+    assert_eq!(out.matches("abcdefgh").count(), 1);
+}
+
+#[test]
+fn reset_starts_over_once_confirmed() {
+    let out = run(&[], "e2e4 e7e5\nreset\nn\nfen\nreset\ny\nfen\nmoves\n");
+    assert!(out.contains("Position: rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2"));
+    assert!(out.contains("Position: rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"));
+    assert!(out.ends_with("Moves played: \n"));
+}
+
+#[test]
+fn kqvk_endgame_loads() {
+    let out = run(&[], "endgame KQvK\nfen\n");
+    let fen = out.lines().find_map(|line| line.strip_prefix("Position: ")).unwrap();
+    assert_eq!(fen, "8/8/8/4k3/8/8/8/3QK3 w - - 0 1");
+    let mut pieces: Vec<_> = fen.split(' ').next().unwrap().chars().filter(|c| c.is_alphabetic()).collect();
+    pieces.sort();
+    assert_eq!(pieces, vec!['K', 'Q', 'k']);
+}
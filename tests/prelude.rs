@@ -0,0 +1,18 @@
+use chess::prelude::*;
+
+// `e2` as a Location, column then row from rank 8
+fn square(name: &str) -> Location {
+    let bytes = name.as_bytes();
+    ((bytes[0] - b'a') as usize, (b'8' - bytes[1]) as usize)
+}
+
+#[test]
+fn fools_mate() {
+    let mut game = Game::new();
+    for (from, to) in [("f2", "f3"), ("e7", "e5"), ("g2", "g4"), ("d8", "h4")] {
+        assert_eq!(game.result(), GameResult::Ongoing);
+        apply_move(&mut game, square(from), square(to), None).unwrap();
+    }
+    assert!(all_legal_moves(&game, Color::White).is_empty());
+    assert_eq!(game.result(), GameResult::Checkmate(Color::Black));
+}